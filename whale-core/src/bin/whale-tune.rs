@@ -0,0 +1,252 @@
+//! Texel tuning of the evaluation weights: fit them to the results of games, so the evaluation
+//! of each position predicts the result through a logistic curve as well as it can
+//!
+//! ```text
+//! whale-tune <positions.epd | games.pgn> [passes]
+//! ```
+//!
+//! EPD records carry their result in a `c9` or `result` operation, like `c9 "1-0";`. Games of a
+//! PGN file label every position of their main line after the opening with their result. The
+//! tuned weights are printed one per line as their name and value
+//!
+//! The evaluation has no piece-square tables, so there are none to tune: the weights are the
+//! piece values and the bonuses and penalties of `EvalParams`
+
+use std::time::Instant;
+use whale_core::chess_engine::is_in_check;
+use whale_core::chess_eval::{evaluate_with, EvalParams};
+use whale_core::chess_game::GameResult;
+use whale_core::chess_notation::{read_epd, read_pgn};
+use whale_core::chess_parts::*;
+
+/// Plies of each game left out, as opening theory says more about the players than the position
+const SKIPPED_OPENING_PLIES: usize = 8;
+
+/// Rounds over all weights when not told otherwise
+const DEFAULT_PASSES: usize = 20;
+
+/// Steps each weight is tried with, coarsest first
+const STEPS: [i32; 4] = [8, 4, 2, 1];
+
+/// A position and the result of its game for White: 1 for a win, 0.5 for a draw and 0 for a loss
+type Sample = (Board, f64);
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(path) = args.get(1) else {
+        eprintln!("usage: whale-tune <positions.epd | games.pgn> [passes]");
+        std::process::exit(2);
+    };
+    let passes = args.get(2).and_then(|passes| passes.parse().ok()).unwrap_or(DEFAULT_PASSES);
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("cannot read {}: {}", path, error);
+            std::process::exit(1);
+        },
+    };
+    let samples = if path.ends_with(".pgn") { pgn_samples(&text) } else { epd_samples(&text) };
+    if samples.is_empty() {
+        eprintln!("no labeled positions in {}", path);
+        std::process::exit(1);
+    }
+    eprintln!("{} positions", samples.len());
+
+    let mut params = EvalParams::default();
+    let scaling = fit_scaling(&samples, &params);
+    let mut best = error(&samples, &params, scaling);
+    eprintln!("scaling {:.3}, error {:.6}", scaling, best);
+    // Local search: nudge each weight up or down and keep whatever lowers the error, with finer
+    // steps once the coarse ones stop helping
+    let start = Instant::now();
+    for step in STEPS {
+        for pass in 0..passes {
+            let error = tune_pass(&samples, &mut params, scaling, step, best);
+            let improved = error < best;
+            best = error;
+            eprintln!("step {}, pass {}: error {:.6} after {:.0?}", step, pass + 1, best, start.elapsed());
+            if !improved {
+                break;
+            }
+        }
+    }
+    for (name, value) in params.weights_mut() {
+        println!("{} {}", name, value);
+    }
+}
+
+/// Nudge each weight of `params` by `step` up or down, keeping the changes that lower the error
+/// from `best`, and return the error the weights end up with
+fn tune_pass(samples: &[Sample], params: &mut EvalParams, scaling: f64, step: i32, mut best: f64) -> f64 {
+    for index in 0..params.weights_mut().len() {
+        for delta in [step, -step] {
+            let mut candidate = params.clone();
+            *candidate.weights_mut()[index].1 += delta;
+            let candidate_error = error(samples, &candidate, scaling);
+            if candidate_error < best {
+                best = candidate_error;
+                *params = candidate;
+                break;
+            }
+        }
+    }
+    best
+}
+
+/// Labeled positions of EPD records with a `c9` or `result` operation
+fn epd_samples(text: &str) -> Vec<Sample> {
+    text.lines()
+        .filter_map(|line| read_epd(line).ok())
+        .filter_map(|epd| {
+            let result = result_score(epd.operation("c9").or_else(|| epd.operation("result"))?.first()?)?;
+            Some((epd.board, result))
+        })
+        .filter(|(board, _)| !is_in_check(board, board.turn()))
+        .collect()
+}
+
+/// Positions of the main lines of the games of a PGN, labeled with their results
+fn pgn_samples(text: &str) -> Vec<Sample> {
+    let mut games = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        if line.starts_with("[Event ") && !current.trim().is_empty() {
+            games.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    games.push(current);
+    let mut samples = Vec::new();
+    for game in games.iter().filter_map(|pgn| read_pgn(pgn).ok()) {
+        let result = match game.result() {
+            GameResult::WhiteWins(_) => 1.0,
+            GameResult::BlackWins(_) => 0.0,
+            GameResult::Draw(_) => 0.5,
+            _ => continue,
+        };
+        let mut board = game.start().clone();
+        for (ply, mv) in game.moves().iter().enumerate() {
+            board.make_move(mv);
+            if ply + 1 >= SKIPPED_OPENING_PLIES && !is_in_check(&board, board.turn()) {
+                samples.push((board.clone(), result));
+            }
+        }
+    }
+    samples
+}
+
+/// Result for White of a result written like in PGN
+fn result_score(result: &str) -> Option<f64> {
+    match result {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => None,
+    }
+}
+
+/// Expected result for White of a position White evaluates at `score`
+fn expected_result(score: i32, scaling: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-scaling * score as f64 / 400.0))
+}
+
+/// Mean squared difference between the results and what the evaluation with `params` expects,
+/// computed on all cores
+fn error(samples: &[Sample], params: &EvalParams, scaling: f64) -> f64 {
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk = samples.len().div_ceil(threads);
+    let total: f64 = std::thread::scope(|scope| {
+        let workers: Vec<_> = samples
+            .chunks(chunk)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|(board, result)| {
+                            let score = evaluate_with(board, params);
+                            let white_score = if board.turn() == Color::White { score } else { -score };
+                            (result - expected_result(white_score, scaling)).powi(2)
+                        })
+                        .sum::<f64>()
+                })
+            })
+            .collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+    });
+    total / samples.len() as f64
+}
+
+/// Scaling of the logistic curve that fits the evaluation with `params` best, which the weights
+/// are then tuned with so they keep their centipawn scale
+fn fit_scaling(samples: &[Sample], params: &EvalParams) -> f64 {
+    let (mut low, mut high) = (0.1, 3.0);
+    // The error is convex in the scaling, so a ternary search closes in on its minimum
+    for _ in 0..40 {
+        let first = low + (high - low) / 3.0;
+        let second = high - (high - low) / 3.0;
+        if error(samples, params, first) < error(samples, params, second) {
+            high = second;
+        } else {
+            low = first;
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Positions from even to a queen up, for either side
+    const FENS: [&str; 6] = [
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+        "rnbqkbnr/ppp1pppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1",
+        "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "4k3/8/8/3r4/8/8/4P3/4K3 w - - 0 1",
+    ];
+
+    fn white_score(board: &Board, params: &EvalParams) -> i32 {
+        let score = evaluate_with(board, params);
+        if board.turn() == Color::White { score } else { -score }
+    }
+
+    #[test]
+    fn labeling_epd_records() {
+        let samples = epd_samples(concat!(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - c9 \"1-0\";\n",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - result \"1/2-1/2\";\n",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"unlabeled\";\n",
+        ));
+        let labels: Vec<f64> = samples.iter().map(|(_, result)| *result).collect();
+        assert_eq!(labels, [1.0, 0.5]);
+        assert_eq!(samples[0].0.to_fen(), FENS[0]);
+    }
+
+    #[test]
+    fn fitting_the_scaling_of_known_results() {
+        let params = EvalParams::default();
+        let samples: Vec<Sample> = FENS
+            .iter()
+            .map(|fen| {
+                let board = Board::from_fen(fen).unwrap();
+                let result = expected_result(white_score(&board, &params), 1.3);
+                (board, result)
+            })
+            .collect();
+        assert!((fit_scaling(&samples, &params) - 1.3).abs() < 0.01);
+    }
+
+    #[test]
+    fn a_pass_never_adds_error() {
+        // Results the default weights do not quite predict, so there is something to tune
+        let samples: Vec<Sample> = FENS.iter().map(|fen| Board::from_fen(fen).unwrap()).zip([0.5, 0.5, 1.0, 0.0, 0.5, 0.0]).collect();
+        let mut params = EvalParams::default();
+        let before = error(&samples, &params, 1.0);
+        let after = tune_pass(&samples, &mut params, 1.0, 8, before);
+        assert!(after <= before);
+        assert_eq!(after, error(&samples, &params, 1.0));
+    }
+}
@@ -0,0 +1,297 @@
+//! UCI front end, so chess GUIs and tournament managers can run the engine: commands are read
+//! from standard input and answered on standard output
+//!
+//! ```text
+//! whale-uci
+//! ```
+//!
+//! Besides `Ponder`, the `Skill Level` option weakens play from 20, full strength, down to 0, and
+//! `Contempt` sets the centipawns the engine gives up to avoid a draw, or to get one when negative
+
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use whale_core::chess_engine::legal_move;
+use whale_core::chess_game::History;
+use whale_core::chess_parts::*;
+use whale_core::chess_search::*;
+use whale_core::chess_tt::TranspositionTable;
+
+/// Largest `Contempt` either way, the same range the app offers
+const MAX_CONTEMPT: i32 = 200;
+
+/// Settings changed with `setoption`
+struct Options {
+    skill_level: u8,
+    contempt: i32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { skill_level: MAX_SKILL_LEVEL, contempt: 0 }
+    }
+}
+
+impl Options {
+    /// The options as `uci` lists them
+    fn to_uci() -> Vec<String> {
+        vec![
+            "option name Ponder type check default false".to_string(),
+            format!("option name Skill Level type spin default {} min 0 max {}", MAX_SKILL_LEVEL, MAX_SKILL_LEVEL),
+            format!("option name Contempt type spin default 0 min {} max {}", -MAX_CONTEMPT, MAX_CONTEMPT),
+        ]
+    }
+
+    /// Set the option called `name`, ignoring case as UCI asks, to `value`. Unknown names and
+    /// invalid values are reported as the reason they were not taken
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name.to_ascii_lowercase().as_str() {
+            // The search ponders whenever told to with `go ponder`, so there is nothing to set
+            "ponder" => Ok(()),
+            "skill level" => {
+                let level = value.parse().ok().filter(|&level| level <= MAX_SKILL_LEVEL);
+                self.skill_level = level.ok_or_else(|| format!("invalid Skill Level '{}'", value))?;
+                Ok(())
+            },
+            "contempt" => {
+                let contempt = value.parse().ok().filter(|contempt: &i32| contempt.abs() <= MAX_CONTEMPT);
+                self.contempt = contempt.ok_or_else(|| format!("invalid Contempt '{}'", value))?;
+                Ok(())
+            },
+            _ => Err(format!("unknown option '{}'", name)),
+        }
+    }
+}
+
+/// Name and value of a `setoption` command, without the `setoption` itself. Names and values
+/// may hold spaces
+fn parse_setoption(args: &[&str]) -> Option<(String, String)> {
+    let name_start = args.iter().position(|&arg| arg == "name")? + 1;
+    let value_start = args.iter().position(|&arg| arg == "value");
+    let name = args[name_start..value_start.unwrap_or(args.len())].join(" ");
+    let value = value_start.map_or(String::new(), |start| args[start + 1..].join(" "));
+    Some((name, value))
+}
+
+/// Position of a `position` command, without the `position` itself, and the repetition history
+/// up to it. Moves stop at the first that is not legal, which is the error
+fn parse_position(args: &[&str]) -> Result<(Board, History), String> {
+    let moves_start = args.iter().position(|&arg| arg == "moves").unwrap_or(args.len());
+    let mut board = match args.first() {
+        Some(&"startpos") => Board::default(),
+        Some(&"fen") => Board::from_fen(&args[1..moves_start].join(" ")).map_err(|error| error.to_string())?,
+        _ => return Err("expected startpos or fen".to_string()),
+    };
+    let mut history = History::new(&board);
+    for &uci in args.iter().skip(moves_start + 1) {
+        let Some(mv) = Move::from_uci(&board, uci).and_then(|mv| legal_move(&board, &mv)) else {
+            return Err(format!("illegal move '{}'", uci));
+        };
+        board.make_move(&mv);
+        history.push(&board);
+    }
+    Ok((board, history))
+}
+
+/// What a `go` command asks for, without the `go` itself
+struct Go {
+    limits: SearchLimits,
+    /// Search on the opponent's time until `ponderhit` or `stop`
+    ponder: bool,
+    /// Time to think, from the clocks or `movetime`, which a pondering search only gets once
+    /// the expected move is played
+    thinking_time: Option<Duration>,
+}
+
+fn parse_go(args: &[&str], turn: Color) -> Go {
+    let mut limits = SearchLimits::default();
+    let (mut ponder, mut move_time, mut moves_to_go) = (false, None, None);
+    let mut clocks = [Duration::ZERO; 2];
+    let mut increments = [Duration::ZERO; 2];
+    let mut has_clock = false;
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let mut number = || args.next().and_then(|value| value.parse::<u64>().ok());
+        match arg {
+            "infinite" => limits.infinite = true,
+            "ponder" => ponder = true,
+            "depth" => limits.depth = number().map(|depth| depth as u32),
+            "nodes" => limits.nodes = number(),
+            "movetime" => move_time = number().map(Duration::from_millis),
+            "movestogo" => moves_to_go = number().map(|moves| moves as u32),
+            "wtime" | "btime" | "winc" | "binc" => {
+                let color = if arg.starts_with('w') { Color::White } else { Color::Black };
+                let time = Duration::from_millis(number().unwrap_or(0));
+                if arg.ends_with("time") {
+                    clocks[color as usize] = time;
+                    has_clock |= color == turn;
+                } else {
+                    increments[color as usize] = time;
+                }
+            },
+            _ => {},
+        }
+    }
+    let thinking_time = move_time.or_else(|| has_clock.then(|| allocate_time(clocks[turn as usize], increments[turn as usize], moves_to_go)));
+    if !ponder {
+        limits.move_time = thinking_time;
+    }
+    Go { limits, ponder, thinking_time }
+}
+
+/// A search running on a thread of its own, which answers with `bestmove` when it ends
+struct Running {
+    control: SearchControl,
+    thread: JoinHandle<()>,
+    thinking_time: Option<Duration>,
+}
+
+/// State kept between commands
+struct Engine {
+    board: Board,
+    history: History,
+    options: Options,
+    tt: Arc<Mutex<TranspositionTable>>,
+    search: Option<Running>,
+}
+
+impl Engine {
+    fn new() -> Engine {
+        let board = Board::default();
+        Engine {
+            history: History::new(&board),
+            board,
+            options: Options::default(),
+            tt: Arc::new(Mutex::new(TranspositionTable::new(TT_MEGABYTES))),
+            search: None,
+        }
+    }
+
+    /// Stop the search under way, if any, and wait for its `bestmove`
+    fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.control.stop();
+            search.thread.join().expect("search thread panicked");
+        }
+    }
+
+    fn go(&mut self, go: Go) {
+        self.stop();
+        let limits = SearchLimits { skill_level: Some(self.options.skill_level), contempt: self.options.contempt, ..go.limits };
+        let control = if go.ponder { SearchControl::pondering() } else { SearchControl::default() };
+        let board = self.board.clone();
+        let earlier_positions = self.history.earlier_positions();
+        let tt = self.tt.clone();
+        let thread = {
+            let control = control.clone();
+            std::thread::spawn(move || {
+                let (sender, receiver) = std::sync::mpsc::channel::<SearchInfo>();
+                let printer = std::thread::spawn(move || {
+                    for info in receiver {
+                        info.to_uci().iter().for_each(|line| println!("{}", line));
+                    }
+                });
+                let mut tt = tt.lock().expect("an earlier search thread panicked");
+                // The control holding the sender goes at the end of the statement, which ends
+                // the printer
+                let result = iterative_deepening(&board, &earlier_positions, &limits, &control.with_info(sender), &mut tt);
+                printer.join().expect("info printer panicked");
+                match (&result.best_move, result.ponder_move()) {
+                    (Some(best), Some(reply)) => println!("bestmove {} ponder {}", best.to_uci(), reply.to_uci()),
+                    (Some(best), None) => println!("bestmove {}", best.to_uci()),
+                    (None, _) => println!("bestmove 0000"),
+                }
+            })
+        };
+        self.search = Some(Running { control, thread, thinking_time: go.thinking_time });
+    }
+
+    /// Handle one line of input, returning false on `quit`
+    fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return true;
+        };
+        match command {
+            "uci" => {
+                println!("id name Whale");
+                println!("id author the Whale authors");
+                Options::to_uci().iter().for_each(|line| println!("{}", line));
+                println!("uciok");
+            },
+            "isready" => println!("readyok"),
+            "setoption" => {
+                let result = parse_setoption(args).ok_or_else(|| "expected setoption name".to_string());
+                if let Err(error) = result.and_then(|(name, value)| self.options.set(&name, &value)) {
+                    println!("info string {}", error);
+                }
+            },
+            "ucinewgame" => {
+                self.stop();
+                self.tt.lock().expect("search thread panicked").clear();
+            },
+            "position" => match parse_position(args) {
+                Ok((board, history)) => (self.board, self.history) = (board, history),
+                Err(error) => println!("info string {}", error),
+            },
+            "go" => self.go(parse_go(args, self.board.turn())),
+            "ponderhit" => {
+                if let Some(search) = &self.search {
+                    search.control.ponderhit(search.thinking_time.unwrap_or(Duration::MAX));
+                }
+            },
+            "stop" => self.stop(),
+            "quit" => {
+                self.stop();
+                return false;
+            },
+            _ => println!("info string unknown command '{}'", command),
+        }
+        true
+    }
+}
+
+fn main() {
+    let mut engine = Engine::new();
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !engine.handle(&line) {
+            return;
+        }
+    }
+    engine.stop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_commands() {
+        assert_eq!(parse_setoption(&["name", "Skill", "Level", "value", "5"]), Some(("Skill Level".to_string(), "5".to_string())));
+        let mut options = Options::default();
+        options.set("skill level", "5").unwrap();
+        assert_eq!(options.skill_level, 5);
+        assert!(options.set("Skill Level", "21").is_err());
+        options.set("Contempt", "-50").unwrap();
+        assert_eq!(options.contempt, -50);
+        assert!(options.set("Contempt", "201").is_err());
+        assert!(options.set("Hash", "16").is_err());
+
+        let (board, history) = parse_position(&["startpos", "moves", "g1f3", "g8f6", "f3g1", "f6g8"]).unwrap();
+        assert_eq!(board.repetition_hash(), Board::default().repetition_hash());
+        assert_eq!(history.repetition_count(), 2);
+        assert!(parse_position(&["fen", "8/8/8/4k3/8/8/8/4K3", "w", "-", "-", "0", "1", "moves", "e1e2"]).is_ok());
+        assert_eq!(parse_position(&["startpos", "moves", "e2e5"]).err(), Some("illegal move 'e2e5'".to_string()));
+
+        let go = parse_go(&["wtime", "60000", "btime", "1000", "winc", "0", "binc", "2000"], Color::Black);
+        assert_eq!(go.limits.move_time, Some(allocate_time(Duration::from_secs(1), Duration::from_secs(2), None)));
+        let go = parse_go(&["ponder", "movetime", "500", "depth", "3"], Color::Black);
+        assert!(go.ponder && go.limits.move_time.is_none());
+        assert_eq!((go.thinking_time, go.limits.depth), (Some(Duration::from_millis(500)), Some(3)));
+        assert_eq!(parse_go(&["infinite"], Color::White).thinking_time, None);
+    }
+}
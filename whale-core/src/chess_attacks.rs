@@ -0,0 +1,242 @@
+use once_cell::sync::Lazy;
+use crate::chess_parts::*;
+
+/// Where the attacks of a slider on one square are found in `SliderTables::attacks`: the
+/// blockers that matter are multiplied by the magic number, whose top bits then index the
+/// attacks for those blockers without any two blocker sets with different attacks colliding
+struct Magic {
+    /// Squares whose blockers can stop the slider, leaving out the last square of each line
+    /// which it reaches whatever stands there
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: u64) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+/// Magic bitboard tables of rooks and bishops, by `Mailbox64Index`
+struct SliderTables {
+    rooks: Vec<Magic>,
+    bishops: Vec<Magic>,
+    attacks: Vec<u64>,
+}
+
+static SLIDERS: Lazy<SliderTables> = Lazy::new(|| {
+    let mut attacks = Vec::new();
+    // Fixed seed, so the tables come out the same on every run
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next_random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut magics = |piece: Piece| -> Vec<Magic> {
+        let directions = MOVESETS[piece as usize].0;
+        (0..64).map(|index| find_magic(Mailbox64Index(index), directions, &mut attacks, &mut next_random)).collect()
+    };
+    let rooks = magics(Piece::Rook);
+    let bishops = magics(Piece::Bishop);
+    SliderTables { rooks, bishops, attacks }
+});
+
+/// Squares knights, kings and pawns attack from each square, by `Mailbox64Index`, the pawns
+/// also by `Color`
+struct LeaperTables {
+    knights: [u64; 64],
+    kings: [u64; 64],
+    pawns: [[u64; 64]; 2],
+}
+
+static LEAPERS: Lazy<LeaperTables> = Lazy::new(|| {
+    let table = |offsets: &[(i8, i8)]| -> [u64; 64] {
+        std::array::from_fn(|index| {
+            offsets
+                .iter()
+                .filter_map(|&(dx, dy)| offset_index_2d(Mailbox64Index(index as u8), dx, dy))
+                .fold(0, |attacks, target| attacks | target.bit())
+        })
+    };
+    // Pawns only attack diagonally, not the squares they push to
+    let pawn_table = |color: Color| {
+        let captures: Vec<_> = MOVESETS[Piece::Pawn as usize].0.iter().filter(|&&(dx, _)| dx != 0).map(|&(dx, dy)| (dx, dy * pawn_forward(color))).collect();
+        table(&captures)
+    };
+    LeaperTables {
+        knights: table(MOVESETS[Piece::Knight as usize].0),
+        kings: table(MOVESETS[Piece::King as usize].0),
+        pawns: [pawn_table(Color::Black), pawn_table(Color::White)],
+    }
+});
+
+/// Squares a slider on `square` moving along `directions` attacks with the pieces on `occupied`
+/// in its way, found by walking each line. The first piece on a line is attacked, whatever its
+/// color
+fn sliding_attacks(square: Mailbox64Index, directions: &[(i8, i8)], occupied: u64) -> u64 {
+    let mut attacks = 0;
+    for &(dx, dy) in directions {
+        let mut current = square;
+        while let Some(target) = offset_index_2d(current, dx, dy) {
+            attacks |= target.bit();
+            if occupied & target.bit() != 0 {
+                break;
+            }
+            current = target;
+        }
+    }
+    attacks
+}
+
+/// Try random sparse numbers until one maps every set of blockers of a slider on `square` to
+/// a slot of its own, or shared only with blockers that leave the same attacks, then append
+/// the slots to `attacks`
+fn find_magic(square: Mailbox64Index, directions: &[(i8, i8)], attacks: &mut Vec<u64>, next_random: &mut impl FnMut() -> u64) -> Magic {
+    let mask = directions.iter().fold(0, |mask, &(dx, dy)| {
+        let mut mask = mask;
+        let mut current = square;
+        while let Some(target) = offset_index_2d(current, dx, dy) {
+            if offset_index_2d(target, dx, dy).is_none() {
+                break;
+            }
+            mask |= target.bit();
+            current = target;
+        }
+        mask
+    });
+    // Every subset of the mask, walked with the carry-rippler trick
+    let mut blockers = Vec::new();
+    let mut subset = 0u64;
+    loop {
+        blockers.push((subset, sliding_attacks(square, directions, subset)));
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+    let bits = mask.count_ones();
+    let shift = 64 - bits;
+    let mut slots = vec![(0u32, 0u64); 1 << bits];
+    for attempt in 1.. {
+        let magic = next_random() & next_random() & next_random();
+        // Numbers spreading too few mask bits into the top byte rarely work
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+        let fits = blockers.iter().all(|&(occupied, reached)| {
+            let slot = &mut slots[(occupied.wrapping_mul(magic) >> shift) as usize];
+            if slot.0 != attempt {
+                *slot = (attempt, reached);
+                true
+            } else {
+                slot.1 == reached
+            }
+        });
+        if fits {
+            let offset = attacks.len();
+            attacks.extend(slots.iter().map(|&(_, reached)| reached));
+            return Magic { mask, magic, shift, offset };
+        }
+    }
+    unreachable!()
+}
+
+/// Squares a knight on `square` attacks
+pub fn knight_attacks(square: Mailbox64Index) -> u64 {
+    LEAPERS.knights[square.0 as usize]
+}
+
+/// Squares a king on `square` attacks
+pub fn king_attacks(square: Mailbox64Index) -> u64 {
+    LEAPERS.kings[square.0 as usize]
+}
+
+/// Squares a pawn of `color` on `square` attacks, diagonally in front of it
+pub fn pawn_attacks(square: Mailbox64Index, color: Color) -> u64 {
+    LEAPERS.pawns[color as usize][square.0 as usize]
+}
+
+/// Squares a rook on `square` attacks, with the pieces on `occupied` in its way. The first
+/// piece on each line is included, whatever its color
+pub fn rook_attacks(square: Mailbox64Index, occupied: u64) -> u64 {
+    let tables = &*SLIDERS;
+    tables.attacks[tables.rooks[square.0 as usize].index(occupied)]
+}
+
+/// Squares a bishop on `square` attacks, with the pieces on `occupied` in its way. The first
+/// piece on each line is included, whatever its color
+pub fn bishop_attacks(square: Mailbox64Index, occupied: u64) -> u64 {
+    let tables = &*SLIDERS;
+    tables.attacks[tables.bishops[square.0 as usize].index(occupied)]
+}
+
+/// Squares a queen on `square` attacks, with the pieces on `occupied` in its way
+pub fn queen_attacks(square: Mailbox64Index, occupied: u64) -> u64 {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+/// Squares a sliding `piece` on `square` attacks, with the pieces on `occupied` in its way.
+/// None for the other pieces
+pub fn slider_attacks(piece: Piece, square: Mailbox64Index, occupied: u64) -> u64 {
+    match piece {
+        Piece::Rook => rook_attacks(square, occupied),
+        Piece::Bishop => bishop_attacks(square, occupied),
+        Piece::Queen => queen_attacks(square, occupied),
+        _ => 0,
+    }
+}
+
+/// Squares strictly between `from` and `to` when they share a rank, file or diagonal, and none
+/// otherwise
+pub fn between(from: Mailbox64Index, to: Mailbox64Index) -> u64 {
+    if rook_attacks(from, 0) & to.bit() != 0 {
+        rook_attacks(from, to.bit()) & rook_attacks(to, from.bit())
+    } else if bishop_attacks(from, 0) & to.bit() != 0 {
+        bishop_attacks(from, to.bit()) & bishop_attacks(to, from.bit())
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magics_match_walking_the_lines() {
+        let mut state = 0x1234_5678_9abc_def0u64;
+        let mut next_random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for index in 0..64 {
+            let square = Mailbox64Index(index);
+            for _ in 0..100 {
+                let occupied = next_random() & next_random();
+                assert_eq!(rook_attacks(square, occupied), sliding_attacks(square, MOVESETS[Piece::Rook as usize].0, occupied));
+                assert_eq!(bishop_attacks(square, occupied), sliding_attacks(square, MOVESETS[Piece::Bishop as usize].0, occupied));
+            }
+        }
+        let square = |name| Mailbox64Index::try_from(name).unwrap();
+        assert_eq!(between(square("a1"), square("a4")), square("a2").bit() | square("a3").bit());
+        assert_eq!(between(square("h8"), square("e5")), square("g7").bit() | square("f6").bit());
+        assert_eq!(between(square("a1"), square("b3")), 0);
+    }
+
+    #[test]
+    fn leaper_attacks() {
+        let square = |name: &str| Mailbox64Index::try_from(name).unwrap();
+        let bits = |names: &[&str]| names.iter().fold(0, |bitboard, &name| bitboard | square(name).bit());
+        assert_eq!(knight_attacks(square("a1")), bits(&["b3", "c2"]));
+        assert_eq!(knight_attacks(square("e4")).count_ones(), 8);
+        assert_eq!(king_attacks(square("h8")), bits(&["g8", "g7", "h7"]));
+        assert_eq!(pawn_attacks(square("e4"), Color::White), bits(&["d5", "f5"]));
+        assert_eq!(pawn_attacks(square("a7"), Color::Black), bits(&["b6"]));
+    }
+}
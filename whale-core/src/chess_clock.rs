@@ -0,0 +1,175 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// A stretch of a time control, like 40 moves in 90 minutes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimePeriod {
+    /// Moves to make within the period, or `None` when it lasts the rest of the game
+    pub moves: Option<u32>,
+    /// Time added to the clock when the period starts
+    pub time: Duration,
+    /// Time added after each move of the period
+    pub increment: Duration,
+    /// Time at the start of each move of the period before the clock starts running
+    pub delay: Duration,
+}
+
+/// How much time the players get: periods played in turn, the last one repeating once its
+/// moves are made
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeControl {
+    /// Periods in the order they are played, never empty
+    pub periods: Vec<TimePeriod>,
+}
+
+impl TimeControl {
+    /// `time` for the whole game, with `increment` added after each move
+    pub fn sudden_death(time: Duration, increment: Duration) -> TimeControl {
+        TimeControl {
+            periods: vec![TimePeriod { moves: None, time, increment, delay: Duration::ZERO }],
+        }
+    }
+
+    /// The same time control with `delay` before the clock runs on every move
+    pub fn with_delay(mut self, delay: Duration) -> TimeControl {
+        for period in &mut self.periods {
+            period.delay = delay;
+        }
+        self
+    }
+
+    /// Parse the value of a PGN `TimeControl` tag, like "40/5400+30:1800+30" or "300+2".
+    /// Unknown, untimed and sandglass controls have no `TimeControl`
+    pub fn from_pgn(value: &str) -> Option<TimeControl> {
+        let mut periods = Vec::new();
+        for field in value.split(':') {
+            let (moves, rest) = match field.split_once('/') {
+                Some((moves, rest)) => (Some(moves.parse().ok().filter(|&moves| moves > 0)?), rest),
+                None => (None, field),
+            };
+            let (time, increment) = rest.split_once('+').unwrap_or((rest, "0"));
+            let seconds = |text: &str| text.parse::<f64>().ok().filter(|seconds| seconds.is_finite() && *seconds >= 0.0).map(Duration::from_secs_f64);
+            periods.push(TimePeriod { moves, time: seconds(time)?, increment: seconds(increment)?, delay: Duration::ZERO });
+        }
+        Some(TimeControl { periods })
+    }
+
+    /// Value of the PGN `TimeControl` tag describing the control. PGN has no way to give a delay,
+    /// so it is left out
+    pub fn to_pgn(&self) -> String {
+        let fields: Vec<String> = self
+            .periods
+            .iter()
+            .map(|period| {
+                let mut field = String::new();
+                if let Some(moves) = period.moves {
+                    field.push_str(&format!("{}/", moves));
+                }
+                field.push_str(&period.time.as_secs_f64().to_string());
+                if !period.increment.is_zero() {
+                    field.push_str(&format!("+{}", period.increment.as_secs_f64()));
+                }
+                field
+            })
+            .collect();
+        fields.join(":")
+    }
+}
+
+/// One player's clock under a `TimeControl`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Clock {
+    remaining: Duration,
+    /// Index of the current period in the time control
+    period: usize,
+    /// Moves made in the current period
+    moves: u32,
+    flagged: bool,
+}
+
+impl Clock {
+    /// Clock at the start of a game played with `control`
+    pub fn new(control: &TimeControl) -> Clock {
+        Clock {
+            remaining: control.periods[0].time,
+            period: 0,
+            moves: 0,
+            flagged: false,
+        }
+    }
+
+    /// Time left when the clock is not running
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    /// Time left after thinking for `elapsed` on the current move, which is less than `elapsed`
+    /// off `remaining` when the period has a delay
+    pub fn remaining_after(&self, control: &TimeControl, elapsed: Duration) -> Duration {
+        self.remaining.saturating_sub(elapsed.saturating_sub(control.periods[self.period].delay))
+    }
+
+    /// Whether the player ran out of time on a move
+    pub fn is_flagged(&self) -> bool {
+        self.flagged
+    }
+
+    /// Period of `control` the clock is in
+    pub fn period<'a>(&self, control: &'a TimeControl) -> &'a TimePeriod {
+        &control.periods[self.period]
+    }
+
+    /// Moves still to make before the next period starts, if the current period has a move count
+    pub fn moves_to_go(&self, control: &TimeControl) -> Option<u32> {
+        control.periods[self.period].moves.map(|moves| moves - self.moves)
+    }
+
+    /// Charge a move that took `elapsed`: take the time off, flagging the clock when it runs out,
+    /// then add the increment, and the time of the next period once this one's moves are made
+    pub fn record_move(&mut self, control: &TimeControl, elapsed: Duration) {
+        if self.flagged {
+            return;
+        }
+        let period = &control.periods[self.period];
+        let used = elapsed.saturating_sub(period.delay);
+        if used > self.remaining {
+            self.remaining = Duration::ZERO;
+            self.flagged = true;
+            return;
+        }
+        self.remaining = self.remaining - used + period.increment;
+        self.moves += 1;
+        if period.moves == Some(self.moves) {
+            self.moves = 0;
+            self.period = (self.period + 1).min(control.periods.len() - 1);
+            self.remaining += control.periods[self.period].time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periods_and_pgn() {
+        let control = TimeControl::from_pgn("2/60+5:30").unwrap();
+        assert_eq!(control.to_pgn(), "2/60+5:30");
+        let mut clock = Clock::new(&control);
+        clock.record_move(&control, Duration::from_secs(20));
+        assert_eq!(clock.remaining(), Duration::from_secs(45));
+        assert_eq!(clock.moves_to_go(&control), Some(1));
+        clock.record_move(&control, Duration::from_secs(10));
+        assert_eq!(clock.remaining(), Duration::from_secs(70));
+        clock.record_move(&control, Duration::from_secs(71));
+        assert!(clock.is_flagged());
+        assert!(TimeControl::from_pgn("?").is_none());
+        assert!(TimeControl::from_pgn("-").is_none());
+
+        let control = TimeControl::sudden_death(Duration::from_secs(10), Duration::ZERO).with_delay(Duration::from_secs(5));
+        let mut clock = Clock::new(&control);
+        assert_eq!(clock.remaining_after(&control, Duration::from_secs(7)), Duration::from_secs(8));
+        clock.record_move(&control, Duration::from_secs(3));
+        assert_eq!(clock.remaining(), Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,588 @@
+use crate::chess_attacks::*;
+use crate::chess_parts::*;
+
+fn generate_pseudolegal(board: &Board, index: Mailbox64Index) -> Vec<Move> {
+    let mut targets = Vec::new();
+    let (piece, color) = piece_from_u8(board.cells[index.0 as usize]);
+    let is_pawn = piece == Piece::Pawn;
+    let is_king = piece == Piece::King;
+    let own = board.occupancy(color);
+    match piece {
+        Piece::Knight => targets.extend(squares(knight_attacks(index) & !own)),
+        Piece::King => targets.extend(squares(king_attacks(index) & !own)),
+        Piece::Pawn => {
+            for (dx, dy) in MOVESETS[Piece::Pawn as usize].0.iter().filter(|(dx, _)| *dx == 0) {
+                if let Some(target_index) = offset_index_2d(index, *dx, dy * pawn_forward(color)) {
+                    if is_pawn_push_allowed(board, index, target_index) {
+                        targets.push(target_index);
+                    }
+                }
+            }
+            // Diagonal moves need an enemy piece on the target, or the target to be the en
+            // passant square
+            let en_passant = board.en_passant_target_square().map_or(0, |square| square.bit());
+            targets.extend(squares(pawn_attacks(index, color) & (board.occupancy(color.opposite()) | en_passant)));
+        },
+        _ => targets.extend(squares(slider_attacks(piece, index, board.occupied()) & !own)),
+    }
+
+    let mut moves = Vec::new();
+    for target_index in targets {
+        let target = board.cells[target_index.0 as usize];
+        if target != 0 && piece_from_u8(target).1 == color {
+            continue;
+        }
+        // Pawns reaching the last rank must become one of the four promotion pieces
+        if is_pawn && (target_index.0 < 8 || target_index.0 >= 56) {
+            for promotion in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+                moves.push(Move::from_squares(board, index, target_index, Some(promotion)));
+            }
+        } else {
+            moves.push(Move::from_squares(board, index, target_index, None));
+        }
+    }
+    if is_king {
+        moves.extend(castling_moves(board, index));
+    }
+    moves
+}
+
+/// Castling moves of the king on `king`. Every square the king and rook cross or land on
+/// must be empty apart from the two of them, and the king may not start on, cross or land
+/// on an attacked square. Nothing assumes the standard king and rook files, so this also
+/// covers Chess960
+fn castling_moves(board: &Board, king: Mailbox64Index) -> Vec<Move> {
+    let (_, color) = piece_from_u8(board.cells[king.0 as usize]);
+    let enemy = color.opposite();
+    let back_rank = Rank::First.relative(color);
+    let mut moves = Vec::new();
+    if king.rank() != back_rank {
+        return moves;
+    }
+    let castling_availability = board.castling_availability();
+    for kingside in [true, false] {
+        let Some(rook_file) = castling_availability[castling_right(color, kingside)] else {
+            continue;
+        };
+        let rook = Mailbox64Index::from_file_rank(rook_file, back_rank);
+        let king_file = king.file();
+        let (king_to_file, rook_to_file) = if kingside { (File::G, File::F) } else { (File::C, File::D) };
+        let lowest = king_file.min(rook_file).min(king_to_file).min(rook_to_file);
+        let highest = king_file.max(rook_file).max(king_to_file).max(rook_to_file);
+        let path_blocked = File::ALL.into_iter().filter(|file| (lowest..=highest).contains(file)).any(|file| {
+            let square = Mailbox64Index::from_file_rank(file, back_rank);
+            square != king && square != rook && board.cells[square.0 as usize] != 0
+        });
+        if path_blocked {
+            continue;
+        }
+        // The rook is about to leave its square, so it must not shield the king's path
+        let transparent = (1u64 << king.0) | (1u64 << rook.0);
+        let king_path = king_file.min(king_to_file)..=king_file.max(king_to_file);
+        let path_attacked = File::ALL.into_iter().filter(|file| king_path.contains(file)).any(|file| {
+            is_square_attacked_through(board, Mailbox64Index::from_file_rank(file, back_rank), enemy, transparent)
+        });
+        if !path_attacked {
+            moves.push(Move::castling(king, kingside));
+        }
+    }
+    moves
+}
+
+/// Pushes never capture, and a double push must start on the pawn's home row and pass an empty square
+fn is_pawn_push_allowed(board: &Board, from: Mailbox64Index, to: Mailbox64Index) -> bool {
+    if board.cells[to.0 as usize] != 0 {
+        return false;
+    }
+    if from.0.abs_diff(to.0) == 16 {
+        let home_rank = if from.0 > to.0 { Rank::Second } else { Rank::Seventh };
+        let passed_square = (from.0 + to.0) / 2;
+        return from.rank() == home_rank && board.cells[passed_square as usize] == 0;
+    }
+    true
+}
+
+/// Whether any piece of `by_color` attacks `index`
+pub fn is_square_attacked(board: &Board, index: Mailbox64Index, by_color: Color) -> bool {
+    is_square_attacked_through(board, index, by_color, 0)
+}
+
+/// Like `is_square_attacked`, but sliders see through the squares set in `transparent`. Used
+/// for king moves, where the king itself must not shield the square it steps to from a
+/// slider behind it
+fn is_square_attacked_through(board: &Board, index: Mailbox64Index, by_color: Color, transparent: u64) -> bool {
+    // Pawns attack diagonally forwards, so the squares they attack this one from are those a
+    // pawn of the other color on it would attack
+    if pawn_attacks(index, by_color.opposite()) & board.bitboard(Piece::Pawn, by_color) != 0
+        || knight_attacks(index) & board.bitboard(Piece::Knight, by_color) != 0
+        || king_attacks(index) & board.bitboard(Piece::King, by_color) != 0
+    {
+        return true;
+    }
+    let queens = board.bitboard(Piece::Queen, by_color);
+    let straight = board.bitboard(Piece::Rook, by_color) | queens;
+    let diagonal = board.bitboard(Piece::Bishop, by_color) | queens;
+    let blockers = board.occupied() & !transparent;
+    rook_attacks(index, blockers) & straight != 0 || bishop_attacks(index, blockers) & diagonal != 0
+}
+
+/// Squares of the pieces of `by_color` attacking `index`, one bit per `Mailbox64Index`, leaving
+/// out the pieces on the squares set in `removed`, which sliders see through
+fn attackers_of(board: &Board, index: Mailbox64Index, by_color: Color, removed: u64) -> u64 {
+    let queens = board.bitboard(Piece::Queen, by_color);
+    let straight = board.bitboard(Piece::Rook, by_color) | queens;
+    let diagonal = board.bitboard(Piece::Bishop, by_color) | queens;
+    let blockers = board.occupied() & !removed;
+    let attackers = pawn_attacks(index, by_color.opposite()) & board.bitboard(Piece::Pawn, by_color)
+        | knight_attacks(index) & board.bitboard(Piece::Knight, by_color)
+        | king_attacks(index) & board.bitboard(Piece::King, by_color)
+        | rook_attacks(index, blockers) & straight
+        | bishop_attacks(index, blockers) & diagonal;
+    attackers & !removed
+}
+
+/// Static exchange evaluation: the material the side to move ends up winning, in centipawns,
+/// when `mv` starts a series of captures on its target square and each side keeps recapturing
+/// with its least valuable attacker for as long as that pays. Pins are not taken into account
+pub fn static_exchange(board: &Board, mv: &Move) -> i32 {
+    let target = mv.to;
+    let mut removed = 1u64 << mv.from.0;
+    if mv.en_passant {
+        removed |= 1 << en_passant_victim_square(mv.from, mv.to).0;
+    }
+    let promotion_gain = mv.promotion.map_or(0, |piece| piece.value() - Piece::Pawn.value());
+    // gains[i] is what the side making capture i wins if the exchange stops right after it
+    let mut gains = vec![mv.captured.map_or(0, |piece| piece.value()) + promotion_gain];
+    let mut on_target = mv.promotion.unwrap_or(mv.piece);
+    let mut side = board.turn().opposite();
+    loop {
+        let attackers = attackers_of(board, target, side, removed);
+        let least_valuable = [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]
+            .into_iter()
+            .find_map(|piece| squares(attackers & board.bitboard(piece, side)).next().map(|square| (square.0, piece)));
+        let Some((square, piece)) = least_valuable else {
+            break;
+        };
+        // The king may only take last, when nothing can take it back
+        if piece == Piece::King && attackers_of(board, target, side.opposite(), removed | (1 << square)) != 0 {
+            break;
+        }
+        gains.push(on_target.value() - gains.last().unwrap());
+        removed |= 1 << square;
+        on_target = piece;
+        side = side.opposite();
+    }
+    // Going back from the last capture, each side takes only when that beats stopping before it
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(last);
+    }
+    gains[0]
+}
+
+/// Squares attacked by the pieces of `color`, one bit per `Mailbox64Index`. Squares holding
+/// pieces of either color count, so defended pieces show up as attacked too
+pub fn attacked_squares(board: &Board, color: Color) -> u64 {
+    let mut attacked = 0u64;
+    for square in board.pieces(color) {
+        attacked |= match piece_from_u8(board.cells[square.0 as usize]).0 {
+            Piece::Pawn => pawn_attacks(square, color),
+            Piece::Knight => knight_attacks(square),
+            Piece::King => king_attacks(square),
+            piece => slider_attacks(piece, square, board.occupied()),
+        };
+    }
+    attacked
+}
+
+/// Square of the king of the given color, if there is one on the board
+pub fn find_king(board: &Board, color: Color) -> Option<Mailbox64Index> {
+    board.king_square(color)
+}
+
+/// Whether the king of the given color is currently attacked
+pub fn is_in_check(board: &Board, color: Color) -> bool {
+    let attacker = color.opposite();
+    match find_king(board, color) {
+        Some(king) => is_square_attacked(board, king, attacker),
+        None => false,
+    }
+}
+
+/// Whether `mv` puts the opponent in check, worked out without playing it. Besides the moved
+/// piece (the rook when castling) attacking the king from where it lands, every square the move
+/// empties can uncover a slider behind it, and en passant empties two squares of a rank at once
+fn gives_check(board: &Board, mv: &Move) -> bool {
+    let (_, color) = piece_from_u8(board.cells[mv.from.0 as usize]);
+    let enemy = color.opposite();
+    let Some(king) = find_king(board, enemy) else {
+        return false;
+    };
+    let (landed_piece, landed_on, mut vacated, mut placed) = if mv.castle {
+        let (rook_from, rook_to) = castling_rook_squares(&board.castling_availability(), mv.from, mv.to);
+        let vacated = (1u64 << mv.from.0) | (1u64 << rook_from.0);
+        // The king only matters as a blocker on its new square
+        (Piece::Rook, rook_to, vacated, (1u64 << rook_to.0) | (1u64 << mv.to.0))
+    } else {
+        let piece = mv.promotion.unwrap_or(mv.piece);
+        (piece, mv.to, 1u64 << mv.from.0, 1u64 << mv.to.0)
+    };
+    if mv.en_passant {
+        vacated |= 1 << en_passant_victim_square(mv.from, mv.to).0;
+    }
+    vacated &= !placed;
+    placed &= !(1u64 << landed_on.0);
+
+    // Pawns and knights can only check directly
+    let direct = match landed_piece {
+        Piece::Pawn => pawn_attacks(landed_on, color),
+        Piece::Knight => knight_attacks(landed_on),
+        _ => 0,
+    };
+    if direct & king.bit() != 0 {
+        return true;
+    }
+
+    // Look out from the king over the position after the move: the first piece on each line
+    // checks if it is a slider of the mover moving along that line, wherever it came from
+    let occupied = (board.occupied() & !vacated) | placed | landed_on.bit();
+    let staying = !vacated & !placed & !landed_on.bit();
+    let landed = |pieces: [Piece; 2]| if pieces.contains(&landed_piece) { landed_on.bit() } else { 0 };
+    let queens = board.bitboard(Piece::Queen, color);
+    let straight = ((board.bitboard(Piece::Rook, color) | queens) & staying) | landed([Piece::Rook, Piece::Queen]);
+    let diagonal = ((board.bitboard(Piece::Bishop, color) | queens) & staying) | landed([Piece::Bishop, Piece::Queen]);
+    rook_attacks(king, occupied) & straight != 0 || bishop_attacks(king, occupied) & diagonal != 0
+}
+
+/// Squares holding a piece of the side to move
+fn pieces_of_side_to_move(board: &Board) -> impl Iterator<Item = Mailbox64Index> {
+    board.pieces(board.turn())
+}
+
+/// Every legal move for the side to move
+pub fn generate_all_legal_moves(board: &Board) -> Vec<Move> {
+    let safety = king_safety(board);
+    let mut moves = Vec::new();
+    for index in pieces_of_side_to_move(board) {
+        generate_legal_into(board, index, &safety, &mut moves);
+    }
+    moves
+}
+
+/// Whether `mv` can be played in `board`: the side to move owns the piece, the piece can reach
+/// the target, special-move conditions hold and the mover's king is not left in check
+pub fn is_legal(board: &Board, mv: &Move) -> bool {
+    legal_move(board, mv).is_some()
+}
+
+/// The generated legal move matching `mv`, if it is legal. Unlike a move built with
+/// `Move::from_squares`, it carries the annotations only the generator fills in, like
+/// `gives_check`
+pub fn legal_move(board: &Board, mv: &Move) -> Option<Move> {
+    generate_legal(board, mv.from)
+        .into_iter()
+        .find(|legal| Move { gives_check: legal.gives_check, ..mv.clone() } == *legal)
+}
+
+/// Whether the side to move has at least one legal move
+pub fn has_legal_move(board: &Board) -> bool {
+    let safety = king_safety(board);
+    let mut moves = Vec::new();
+    pieces_of_side_to_move(board).any(|index| {
+        generate_legal_into(board, index, &safety, &mut moves);
+        !moves.is_empty()
+    })
+}
+
+/// Whether the side to move is in check and cannot get out of it
+pub fn is_checkmate(board: &Board) -> bool {
+    is_in_check(board, board.turn()) && !has_legal_move(board)
+}
+
+/// Whether the side to move is not in check but has no legal move, which draws the game
+pub fn is_stalemate(board: &Board) -> bool {
+    !is_in_check(board, board.turn()) && !has_legal_move(board)
+}
+
+/// Whether neither side has enough material left to ever deliver mate: bare kings, a single
+/// minor piece, or only bishops that all stand on squares of the same color
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let both = |piece: Piece| board.bitboard(piece, Color::White) | board.bitboard(piece, Color::Black);
+    if both(Piece::Pawn) | both(Piece::Rook) | both(Piece::Queen) != 0 {
+        return false;
+    }
+    let (knights, bishops) = (both(Piece::Knight), both(Piece::Bishop));
+    match (knights.count_ones(), bishops) {
+        (0 | 1, 0) => true,
+        (0, _) => bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0,
+        _ => false,
+    }
+}
+
+/// Legal moves of the piece on `index`, which are none unless it belongs to the side to move
+pub fn generate_legal(board: &Board, index: Mailbox64Index) -> Vec<Move> {
+    let mut moves = Vec::new();
+    generate_legal_into(board, index, &king_safety(board), &mut moves);
+    moves
+}
+
+/// Checks and pins against the king of the side to move, worked out once per position so
+/// moves can be tested for legality without playing them out
+struct KingSafety {
+    /// Number of enemy pieces giving check
+    checkers: u32,
+    /// Squares a non-king move has to land on to deal with the check: the checker and the
+    /// squares between it and the king. Every square when not in check
+    check_mask: u64,
+    /// For each square, the squares the piece on it may move to without exposing the king.
+    /// Every square unless the piece is pinned, in which case it is the pin line
+    pin_masks: [u64; 64],
+}
+
+fn king_safety(board: &Board) -> KingSafety {
+    let color = board.turn();
+    let mut checkers = 0;
+    let mut check_mask = u64::MAX;
+    let mut pin_masks = [u64::MAX; 64];
+    let Some(king) = find_king(board, color) else {
+        return KingSafety { checkers, check_mask, pin_masks };
+    };
+    // Enemy pawns attack our king from the row in front of it, which is where a pawn of ours
+    // on the king's square would attack
+    let enemy = color.opposite();
+    let leapers = (pawn_attacks(king, color) & board.bitboard(Piece::Pawn, enemy)) | (knight_attacks(king) & board.bitboard(Piece::Knight, enemy));
+    for checker in squares(leapers) {
+        checkers += 1;
+        check_mask &= checker.bit();
+    }
+
+    // Enemy sliders that would reach the king if only our pieces were out of the way: with
+    // nothing between them they check, with a single piece of ours it is pinned to the line
+    let queens = board.bitboard(Piece::Queen, enemy);
+    let enemies = board.occupancy(enemy);
+    let snipers = (rook_attacks(king, enemies) & (board.bitboard(Piece::Rook, enemy) | queens))
+        | (bishop_attacks(king, enemies) & (board.bitboard(Piece::Bishop, enemy) | queens));
+    for sniper in squares(snipers) {
+        let line = between(king, sniper) | sniper.bit();
+        let blockers = between(king, sniper) & board.occupied();
+        match blockers.count_ones() {
+            0 => {
+                checkers += 1;
+                check_mask &= line;
+            },
+            1 if blockers & board.occupancy(color) != 0 => pin_masks[blockers.trailing_zeros() as usize] = line,
+            _ => {},
+        }
+    }
+    KingSafety { checkers, check_mask, pin_masks }
+}
+
+/// Append the legal moves of the piece on `index` to `moves`
+fn generate_legal_into(board: &Board, index: Mailbox64Index, safety: &KingSafety, moves: &mut Vec<Move>) {
+    let cell = board.cells[index.0 as usize];
+    if cell == 0 || piece_from_u8(cell).1 != board.turn() {
+        return;
+    }
+    let (piece, color) = piece_from_u8(cell);
+    // No single move can capture or block two checkers at once, so under double check
+    // only the king may move
+    if safety.checkers >= 2 && piece != Piece::King {
+        return;
+    }
+    let enemy = color.opposite();
+    for mut mv in generate_pseudolegal(board, index) {
+        let legal = if mv.castle {
+            // castling_moves has already checked the whole path
+            true
+        } else if piece == Piece::King {
+            !is_square_attacked_through(board, mv.to, enemy, 1 << index.0)
+        } else if mv.en_passant {
+            // In check, the capture has to take the checking pawn or block on its landing square
+            let victim = en_passant_victim_square(mv.from, mv.to);
+            let evades = safety.check_mask & ((1u64 << mv.to.0) | (1u64 << victim.0)) != 0;
+            // Taking en passant empties two squares of a rank at once, which can uncover a
+            // check no pin line accounts for, so play it out instead
+            evades && {
+                let mut after = board.clone();
+                after.make_move(&mv);
+                !is_in_check(&after, color)
+            }
+        } else {
+            let to = 1u64 << mv.to.0;
+            safety.check_mask & to != 0 && safety.pin_masks[index.0 as usize] & to != 0
+        };
+        if legal {
+            mv.gives_check = gives_check(board, &mv);
+            moves.push(mv);
+        }
+    }
+}
+
+/// Number of leaf nodes of the legal move tree `depth` plies deep
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = generate_all_legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut board = board.clone();
+    let mut nodes = 0;
+    for mv in moves {
+        let undo = board.make_move(&mv);
+        nodes += perft(&board, depth - 1);
+        board.unmake_move(undo);
+    }
+    nodes
+}
+
+/// Slow reference generator: every pseudolegal move that does not leave the mover's king in
+/// check once played out, with `gives_check` read off the resulting position. Only meant to cross-check `generate_all_legal_moves`
+fn generate_all_legal_moves_reference(board: &Board) -> Vec<Move> {
+    let mover = board.turn();
+    let mut board = board.clone();
+    let mut moves = Vec::new();
+    let opponent = mover.opposite();
+    for index in pieces_of_side_to_move(&board.clone()) {
+        for mut mv in generate_pseudolegal(&board, index) {
+            let undo = board.make_move(&mv);
+            if !is_in_check(&board, mover) {
+                mv.gives_check = is_in_check(&board, opponent);
+                moves.push(mv);
+            }
+            board.unmake_move(undo);
+        }
+    }
+    moves
+}
+
+/// Play `games` random games of up to `plies` plies from `start`, checking at every position
+/// that the legal generator agrees with the slow reference. Returns a description of the
+/// first divergence, including the moves that led to it
+pub fn movegen_self_test(start: &Board, games: usize, plies: usize, seed: u64) -> Result<(), String> {
+    // xorshift64, good enough to pick random moves and keeps the run reproducible
+    let mut state = seed.max(1);
+    let mut next_random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for game in 0..games {
+        let mut board = start.clone();
+        let mut line = Vec::new();
+        for _ in 0..plies {
+            let fast = generate_all_legal_moves(&board);
+            let slow = generate_all_legal_moves_reference(&board);
+            let missing: Vec<_> = slow.iter().filter(|mv| !fast.contains(mv)).map(Move::to_uci).collect();
+            let extra: Vec<_> = fast.iter().filter(|mv| !slow.contains(mv)).map(Move::to_uci).collect();
+            if !missing.is_empty() || !extra.is_empty() || fast.len() != slow.len() {
+                return Err(format!(
+                    "game {} after [{}]: missing [{}], extra [{}], {} moves vs {} in the reference\n{}",
+                    game, line.join(" "), missing.join(" "), extra.join(" "), fast.len(), slow.len(), board,
+                ));
+            }
+            if fast.is_empty() {
+                break;
+            }
+            let mv = &fast[(next_random() % fast.len() as u64) as usize];
+            line.push(mv.to_uci());
+            board.make_move(mv);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let board = Board::from_fen(fen).unwrap();
+        // Shredder-FEN castling letters come back as X-FEN, which means the same rooks
+        if fen.split(' ').nth(2).unwrap().chars().all(|c| "KQkq-".contains(c)) {
+            assert_eq!(board.to_fen(), fen);
+        }
+        assert_eq!(Board::from_fen(&board.to_fen()).unwrap().castling_availability(), board.castling_availability());
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(perft(&board, depth as u32 + 1), nodes, "perft({}) of {}", depth + 1, fen);
+        }
+    }
+
+    #[test]
+    fn perft_startpos() {
+        assert_perft("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        assert_perft("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        assert_perft("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        assert_perft("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        assert_perft("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn perft_position_6() {
+        assert_perft("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", &[46, 2079, 89890]);
+    }
+
+    #[test]
+    fn perft_chess960() {
+        assert_perft("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", &[21, 528, 12189]);
+        assert_perft("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", &[21, 807, 18002]);
+        assert_perft("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", &[20, 479, 10471]);
+        assert_perft("qbbnnrkr/2pp2pp/p7/1pP5/8/8/PP1PPPPP/QBBNNRKR w HFhf b6 0 9", &[27, 793, 20868]);
+        assert_perft("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", &[28, 1120, 31058]);
+        assert_perft("qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9", &[29, 899, 26578]);
+    }
+
+    #[test]
+    fn static_exchange_evaluation() {
+        let see = |fen: &str, uci: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            static_exchange(&board, &legal_move(&board, &Move::from_uci(&board, uci).unwrap()).unwrap())
+        };
+        // An undefended pawn, a pawn defended by a pawn, and a rook behind the queen
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -220);
+        assert_eq!(see("4k3/8/8/3p4/8/8/3R4/3QK3 w - - 0 1", "d2d5"), 100);
+        assert_eq!(see("4k3/8/2p5/3p4/8/8/3R4/3QK3 w - - 0 1", "d2d5"), -300);
+    }
+
+    #[test]
+    fn en_passant_discovers_check_along_the_rank() {
+        let board = Board::from_fen("8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 1").unwrap();
+        let capture = legal_move(&board, &Move::from_uci(&board, "e5d6").unwrap()).unwrap();
+        assert!(capture.en_passant && capture.gives_check);
+        let push = legal_move(&board, &Move::from_uci(&board, "e5e6").unwrap()).unwrap();
+        assert!(!push.gives_check);
+    }
+
+    #[test]
+    fn movegen_matches_reference() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            if let Err(divergence) = movegen_self_test(&Board::from_fen(fen).unwrap(), 20, 200, 0x5eed) {
+                panic!("{}: {}", fen, divergence);
+            }
+        }
+    }
+}
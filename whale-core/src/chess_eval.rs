@@ -0,0 +1,670 @@
+use std::fmt;
+use crate::chess_attacks::pawn_attacks;
+use crate::chess_parts::*;
+
+/// Weights of the evaluation terms in centipawns, which a tuner can adjust
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    /// Value of each piece from pawn to queen, in the order of `Piece`
+    pub piece_values: [i32; 5],
+    /// Bonus for a rook on a file without pawns
+    pub rook_open_file: i32,
+    /// Bonus for a rook on a file without pawns of its own color, but with opponent pawns
+    pub rook_semi_open_file: i32,
+    /// Bonus for a rook on the seventh rank from its side, where it attacks the pawns still at
+    /// home and hems in the king
+    pub rook_on_seventh: i32,
+    /// Bonus for two rooks defending each other along a rank or file
+    pub connected_rooks: i32,
+    /// Bonus for having both bishops, which together cover every square
+    pub bishop_pair: i32,
+    /// Penalty of a bishop per pawn of its own color on squares of the bishop's color, twice
+    /// over when the pawn is blocked and so cannot get out of its way
+    pub bad_bishop_pawn: i32,
+    /// Bonus for a knight on an outpost: a square in the opponent's half defended by a pawn of
+    /// its own that no pawn of the opponent can ever attack
+    pub knight_outpost: i32,
+    /// Extra bonus for an outpost on the central files or the sixth rank, where it does the most
+    pub knight_outpost_strong: i32,
+    /// Bonus for a passed pawn, with no opponent pawn ahead of it on its own or the neighbouring
+    /// files, by its rank from its side
+    pub passed_pawn: [i32; 8],
+    /// Endgame bonus of a passed pawn per square the opponent king is away from the square in
+    /// front of it, by its rank from its side. Halved when an opponent piece blocks the pawn
+    pub passed_pawn_king_distance: [i32; 8],
+    /// Endgame penalty of a passed pawn per square its own king is away from the square in front
+    /// of it, by its rank from its side
+    pub passed_pawn_own_king_distance: [i32; 8],
+    /// Middlegame bonus per safe square in the opponent's half behind the pawns on the central
+    /// files, per knight and bishop of its own that has room to maneuver there
+    pub space: i32,
+    /// Bonus for a passed pawn the opponent king cannot catch, with no pieces left to stop it
+    pub unstoppable_pawn: i32,
+    /// Against a bare king, bonus per square the king is from the center, as mates need it on
+    /// the edge
+    pub mop_up_edge: i32,
+    /// Against a bare king, bonus per square the kings are closer than they can be apart, as
+    /// mates need the other king close
+    pub mop_up_kings: i32,
+    /// Bonus for the side to move, which is a move ahead of where the position stands still
+    pub tempo: i32,
+    /// Share of the evaluation out of `FULL_SCALE` left when each side has only a bishop and
+    /// pawns, with the bishops on squares of different colors, as the weaker side can hold
+    /// blockades on the squares of its own bishop
+    pub opposite_bishops_scale: i32,
+    /// Share of the evaluation out of `FULL_SCALE` left when each side has only a rook and pawns,
+    /// one side a single pawn up with all pawns on the same wing, which rarely wins
+    pub rook_ending_scale: i32,
+}
+
+impl EvalParams {
+    /// The weights `evaluate` uses
+    pub const DEFAULT: EvalParams = EvalParams {
+        piece_values: [100, 330, 500, 320, 900],
+        rook_open_file: 25,
+        rook_semi_open_file: 12,
+        rook_on_seventh: 20,
+        connected_rooks: 15,
+        bishop_pair: 30,
+        bad_bishop_pawn: 4,
+        knight_outpost: 15,
+        knight_outpost_strong: 10,
+        passed_pawn: [0, 5, 10, 20, 35, 60, 100, 0],
+        passed_pawn_king_distance: [0, 0, 5, 10, 20, 30, 40, 0],
+        passed_pawn_own_king_distance: [0, 0, 0, 5, 10, 15, 20, 0],
+        space: 2,
+        unstoppable_pawn: 400,
+        mop_up_edge: 10,
+        mop_up_kings: 4,
+        tempo: 10,
+        opposite_bishops_scale: 32,
+        rook_ending_scale: 40,
+    };
+
+    /// Value of `piece`, 0 for the king which can never be traded
+    pub fn value(&self, piece: Piece) -> i32 {
+        match piece {
+            Piece::King => 0,
+            piece => self.piece_values[piece as usize - 1],
+        }
+    }
+
+    /// Every weight with its name, like "rook_open_file" or "passed_pawn[6]"
+    pub fn weights_mut(&mut self) -> Vec<(String, &mut i32)> {
+        let EvalParams {
+            piece_values,
+            rook_open_file,
+            rook_semi_open_file,
+            rook_on_seventh,
+            connected_rooks,
+            bishop_pair,
+            bad_bishop_pawn,
+            knight_outpost,
+            knight_outpost_strong,
+            passed_pawn,
+            passed_pawn_king_distance,
+            passed_pawn_own_king_distance,
+            space,
+            unstoppable_pawn,
+            mop_up_edge,
+            mop_up_kings,
+            tempo,
+            opposite_bishops_scale,
+            rook_ending_scale,
+        } = self;
+        let mut weights: Vec<(String, &mut i32)> = Vec::new();
+        for (piece, value) in ["pawn", "bishop", "rook", "knight", "queen"].into_iter().zip(piece_values.iter_mut()) {
+            weights.push((format!("{}_value", piece), value));
+        }
+        weights.extend([
+            ("rook_open_file", rook_open_file),
+            ("rook_semi_open_file", rook_semi_open_file),
+            ("rook_on_seventh", rook_on_seventh),
+            ("connected_rooks", connected_rooks),
+            ("bishop_pair", bishop_pair),
+            ("bad_bishop_pawn", bad_bishop_pawn),
+            ("knight_outpost", knight_outpost),
+            ("knight_outpost_strong", knight_outpost_strong),
+            ("space", space),
+            ("unstoppable_pawn", unstoppable_pawn),
+            ("mop_up_edge", mop_up_edge),
+            ("mop_up_kings", mop_up_kings),
+            ("tempo", tempo),
+            ("opposite_bishops_scale", opposite_bishops_scale),
+            ("rook_ending_scale", rook_ending_scale),
+        ].map(|(name, weight)| (name.to_string(), weight)));
+        // Passed pawns never stand on the first or the last rank
+        for (name, values) in [("passed_pawn", passed_pawn), ("passed_pawn_king_distance", passed_pawn_king_distance), ("passed_pawn_own_king_distance", passed_pawn_own_king_distance)] {
+            for (rank, value) in values.iter_mut().enumerate().take(7).skip(1) {
+                weights.push((format!("{}[{}]", name, rank), value));
+            }
+        }
+        weights
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams::DEFAULT
+    }
+}
+
+/// Material of the pieces other than pawns and kings of both sides from which the position is
+/// a middlegame, and up to which it is an endgame, with a blend between the two
+const MIDDLEGAME_MATERIAL: i32 = 5000;
+const ENDGAME_MATERIAL: i32 = 1300;
+
+/// How far into the endgame a position is at most
+const ENDGAME_SCALE: i32 = 256;
+
+/// Share of the evaluation a position without drawish material keeps
+pub const FULL_SCALE: i32 = 64;
+
+/// Part of the evaluation, see `EvalBreakdown`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Term {
+    /// Value of the pieces
+    Material,
+    /// Rooks on open files and the seventh rank, and connected
+    Rooks,
+    /// The bishop pair and bishops hemmed in by their own pawns
+    Bishops,
+    /// Knights on outposts
+    Knights,
+    /// Passed pawns
+    PassedPawns,
+    /// Room behind the pawns for the minor pieces
+    Space,
+    /// Driving a bare king to the edge
+    MopUp,
+    /// Being the side to move
+    Tempo,
+}
+
+impl Term {
+    /// Every term, in the order they are shown
+    pub const ALL: [Term; 8] = [Term::Material, Term::Rooks, Term::Bishops, Term::Knights, Term::PassedPawns, Term::Space, Term::MopUp, Term::Tempo];
+
+    /// Name of the term, like "Passed pawns"
+    pub fn name(self) -> &'static str {
+        match self {
+            Term::Material => "Material",
+            Term::Rooks => "Rooks",
+            Term::Bishops => "Bishops",
+            Term::Knights => "Knights",
+            Term::PassedPawns => "Passed pawns",
+            Term::Space => "Space",
+            Term::MopUp => "Mop-up",
+            Term::Tempo => "Tempo",
+        }
+    }
+}
+
+/// The static evaluation of a position term by term, for each side
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalBreakdown {
+    /// Score of each term for Black and for White, indexed by `Term` and then `Color`
+    pub terms: [[i32; 2]; Term::ALL.len()],
+    /// Side to move, whose point of view `total` takes
+    pub turn: Color,
+    /// Share of the sum of the terms out of `FULL_SCALE` the evaluation keeps, less in drawish
+    /// endings
+    pub scale: i32,
+}
+
+impl EvalBreakdown {
+    /// Score of `term` for `color`
+    pub fn term(&self, term: Term, color: Color) -> i32 {
+        self.terms[term as usize][color as usize]
+    }
+
+    /// Score of `term` for White less that for Black
+    pub fn difference(&self, term: Term) -> i32 {
+        self.term(term, Color::White) - self.term(term, Color::Black)
+    }
+
+    /// The evaluation from White's point of view
+    pub fn white_total(&self) -> i32 {
+        Term::ALL.into_iter().map(|term| self.difference(term)).sum::<i32>() * self.scale / FULL_SCALE
+    }
+
+    /// The evaluation, from the point of view of the side to move as `evaluate` gives it
+    pub fn total(&self) -> i32 {
+        if self.turn == Color::White { self.white_total() } else { -self.white_total() }
+    }
+}
+
+/// One line per term with its score for White, for Black and the difference, then the scale
+/// when the material is drawish and the total from White's point of view
+impl fmt::Display for EvalBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<14}{:>7}{:>7}{:>7}", "Term", "White", "Black", "Total")?;
+        for term in Term::ALL {
+            writeln!(f, "{:<14}{:>7}{:>7}{:>7}", term.name(), self.term(term, Color::White), self.term(term, Color::Black), self.difference(term))?;
+        }
+        if self.scale != FULL_SCALE {
+            writeln!(f, "{:<14}{:>21}", "Scale", format!("{}/{}", self.scale, FULL_SCALE))?;
+        }
+        write!(f, "{:<14}{:>21}", "Total", self.white_total())
+    }
+}
+
+/// Static evaluation of `board` in centipawns, from the point of view of the side to move:
+/// the score of the side to move less that of the opponent. Every term treats both sides alike,
+/// so the mirrored position with the other side to move evaluates the same
+pub fn evaluate(board: &Board) -> i32 {
+    evaluate_with(board, &EvalParams::DEFAULT)
+}
+
+/// Static evaluation of `board` like `evaluate`, with the weights of `params`
+pub fn evaluate_with(board: &Board, params: &EvalParams) -> i32 {
+    let endgame = endgame(board);
+    let score = |color: Color| side_terms(board, params, color, endgame).iter().sum::<i32>();
+    (score(board.turn()) - score(board.turn().opposite())) * scale(board, params) / FULL_SCALE
+}
+
+/// Static evaluation of `board` with the weights of `params`, term by term
+pub fn evaluate_terms(board: &Board, params: &EvalParams) -> EvalBreakdown {
+    let endgame = endgame(board);
+    let [black, white] = [Color::Black, Color::White].map(|color| side_terms(board, params, color, endgame));
+    EvalBreakdown { terms: std::array::from_fn(|term| [black[term], white[term]]), turn: board.turn(), scale: scale(board, params) }
+}
+
+/// Share of the evaluation out of `FULL_SCALE` to keep for the material on `board`, less in
+/// endings that are hard to win whatever the terms say
+fn scale(board: &Board, params: &EvalParams) -> i32 {
+    let [white, black] = [Color::White, Color::Black].map(|color| board.occupancy(color) & !board.bitboard(Piece::Pawn, color) & !board.bitboard(Piece::King, color));
+    if white.count_ones() != 1 || black.count_ones() != 1 {
+        return FULL_SCALE;
+    }
+    let both = |piece: Piece| [board.bitboard(piece, Color::White), board.bitboard(piece, Color::Black)];
+    if both(Piece::Bishop) == [white, black] && (white & LIGHT_SQUARES == 0) != (black & LIGHT_SQUARES == 0) {
+        return params.opposite_bishops_scale;
+    }
+    let [white_pawns, black_pawns] = both(Piece::Pawn);
+    let queenside = [File::A, File::B, File::C, File::D].into_iter().fold(0, |mask, file| mask | file.bitboard());
+    let pawns = white_pawns | black_pawns;
+    let pawn_up = white_pawns.count_ones().abs_diff(black_pawns.count_ones()) == 1;
+    let one_wing = pawns & queenside == 0 || pawns & !queenside == 0;
+    if both(Piece::Rook) == [white, black] && pawn_up && one_wing {
+        return params.rook_ending_scale;
+    }
+    FULL_SCALE
+}
+
+/// How far `board` is into the endgame, from 0 in the middlegame to `ENDGAME_SCALE` once few
+/// pieces are left
+fn endgame(board: &Board) -> i32 {
+    let material = pieces_material(board, Color::White) + pieces_material(board, Color::Black);
+    let material = material.clamp(ENDGAME_MATERIAL, MIDDLEGAME_MATERIAL);
+    (MIDDLEGAME_MATERIAL - material) * ENDGAME_SCALE / (MIDDLEGAME_MATERIAL - ENDGAME_MATERIAL)
+}
+
+/// Value of the pieces of `color` other than pawns and the king
+fn pieces_material(board: &Board, color: Color) -> i32 {
+    [Piece::Bishop, Piece::Rook, Piece::Knight, Piece::Queen].into_iter().map(|piece| piece.value() * board.bitboard(piece, color).count_ones() as i32).sum()
+}
+
+/// Material of `color` and how well its pieces are placed, `endgame` into the endgame, by
+/// `Term`
+fn side_terms(board: &Board, params: &EvalParams, color: Color, endgame: i32) -> [i32; Term::ALL.len()] {
+    let mut terms = [0; Term::ALL.len()];
+    let mut rooks = Vec::new();
+    let mut bishops = 0;
+    for square in board.pieces(color) {
+        let (piece, _) = board.piece_at(square).unwrap();
+        terms[Term::Material as usize] += params.value(piece);
+        match piece {
+            Piece::Rook => {
+                terms[Term::Rooks as usize] += rook_placement(board, params, square, color);
+                rooks.push(square);
+            },
+            Piece::Bishop => {
+                terms[Term::Bishops as usize] -= bad_bishop(board, params, square, color);
+                bishops += 1;
+            },
+            Piece::Knight => terms[Term::Knights as usize] += knight_outpost(board, params, square, color),
+            Piece::Pawn if is_passed(board, square, color) => {
+                terms[Term::PassedPawns as usize] += passed_pawn(board, params, square, color, endgame);
+            },
+            _ => {},
+        }
+    }
+    if bishops >= 2 {
+        terms[Term::Bishops as usize] += params.bishop_pair;
+    }
+    if let [first, second] = rooks[..] {
+        if sees_along_line(board, first, second) {
+            terms[Term::Rooks as usize] += params.connected_rooks;
+        }
+    }
+    terms[Term::Space as usize] = space(board, params, color) * (ENDGAME_SCALE - endgame) / ENDGAME_SCALE;
+    terms[Term::MopUp as usize] = mop_up(board, params, color);
+    if board.turn() == color {
+        terms[Term::Tempo as usize] = params.tempo;
+    }
+    terms
+}
+
+/// Bonus of `color` for driving a bare opponent king to the edge and its own king towards it,
+/// when it has pieces worth at least a rook to mate with
+fn mop_up(board: &Board, params: &EvalParams, color: Color) -> i32 {
+    let opponent = color.opposite();
+    if board.occupancy(opponent).count_ones() > 1 || pieces_material(board, color) < Piece::Rook.value() {
+        return 0;
+    }
+    let (Some(own_king), Some(opponent_king)) = (board.king_square(color), board.king_square(opponent)) else {
+        return 0;
+    };
+    // From the nearest of the four center squares, 0 to 3
+    let from_center = |index: u8| (2 * index as i32 - 7).abs() / 2;
+    let edge = from_center(opponent_king.file().index()) + from_center(opponent_king.rank().index());
+    params.mop_up_edge * edge + params.mop_up_kings * (7 - distance(own_king, opponent_king))
+}
+
+/// Middlegame bonus of `color` for the safe squares in the opponent's half behind its pawns on the
+/// central files, which its knights and bishops can use
+fn space(board: &Board, params: &EvalParams, color: Color) -> i32 {
+    let minors = (board.bitboard(Piece::Knight, color) | board.bitboard(Piece::Bishop, color)).count_ones() as i32;
+    if minors == 0 {
+        return 0;
+    }
+    let opponent = color.opposite();
+    let mut safe = 0;
+    for file in File::ALL.into_iter().filter(|file| (File::C..=File::F).contains(file)) {
+        let pawns = board.bitboard(Piece::Pawn, color) & file.bitboard();
+        let Some(front) = squares(pawns).map(|pawn| pawn.rank().relative(color)).max() else {
+            continue;
+        };
+        for rank in Rank::ALL.into_iter().filter(|rank| (Rank::Fifth..front).contains(&rank.relative(color))) {
+            let square = Mailbox64Index::from_file_rank(file, rank);
+            if pawns & square.bit() == 0 && !attacked_by_pawn(board, square, opponent) {
+                safe += 1;
+            }
+        }
+    }
+    safe * minors * params.space
+}
+
+/// Bonus of a rook of `color` on `square` for the file and rank it stands on
+fn rook_placement(board: &Board, params: &EvalParams, square: Mailbox64Index, color: Color) -> i32 {
+    let mut score = 0;
+    let file = square.file();
+    if !has_pawn_on_file(board, color, file) {
+        score += if has_pawn_on_file(board, color.opposite(), file) { params.rook_semi_open_file } else { params.rook_open_file };
+    }
+    if square.rank() == Rank::Seventh.relative(color) {
+        score += params.rook_on_seventh;
+    }
+    score
+}
+
+/// Penalty of a bishop of `color` on `square` for its own pawns standing on its color
+fn bad_bishop(board: &Board, params: &EvalParams, square: Mailbox64Index, color: Color) -> i32 {
+    let same_color = if LIGHT_SQUARES & square.bit() != 0 { LIGHT_SQUARES } else { !LIGHT_SQUARES };
+    squares(board.bitboard(Piece::Pawn, color) & same_color)
+        .map(|pawn| {
+            let ahead = pawn.rank().offset(forward(color));
+            let blocked = ahead.is_some_and(|rank| board.piece_at(Mailbox64Index::from_file_rank(pawn.file(), rank)).is_some());
+            if blocked { 2 * params.bad_bishop_pawn } else { params.bad_bishop_pawn }
+        })
+        .sum()
+}
+
+/// Bonus of a knight of `color` on `square` for standing on an outpost
+fn knight_outpost(board: &Board, params: &EvalParams, square: Mailbox64Index, color: Color) -> i32 {
+    let rank = square.rank().relative(color);
+    if rank < Rank::Fifth || rank == Rank::Eighth {
+        return 0;
+    }
+    let neighbours = [-1, 1].map(|by| square.file().offset(by));
+    let defended = attacked_by_pawn(board, square, color);
+    // Opponent pawns on the neighbouring files further up the board could still advance to
+    // attack it
+    let files = neighbours.iter().flatten().fold(0, |mask, file| mask | file.bitboard());
+    let attackable = board.bitboard(Piece::Pawn, color.opposite()) & files & ranks_ahead(square.rank(), color) != 0;
+    if !defended || attackable {
+        return 0;
+    }
+    let strong = (File::C..=File::F).contains(&square.file()) || rank == Rank::Sixth;
+    params.knight_outpost + if strong { params.knight_outpost_strong } else { 0 }
+}
+
+/// Whether the pawn of `color` on `square` has no opponent pawn ahead of it on its own or the
+/// neighbouring files
+fn is_passed(board: &Board, square: Mailbox64Index, color: Color) -> bool {
+    let files = [-1, 0, 1].into_iter().filter_map(|by| square.file().offset(by)).fold(0, |mask, file| mask | file.bitboard());
+    board.bitboard(Piece::Pawn, color.opposite()) & files & ranks_ahead(square.rank(), color) == 0
+}
+
+/// Squares of the ranks further up the board than `rank` from the side of `color`
+fn ranks_ahead(rank: Rank, color: Color) -> u64 {
+    Rank::ALL.into_iter().filter(|other| other.relative(color) > rank.relative(color)).fold(0, |mask, other| mask | other.bitboard())
+}
+
+/// Bonus of a passed pawn of `color` on `square`, `endgame` into the endgame. The closer it is
+/// to promoting the more it is worth, and in the endgame even more so with the opponent king far
+/// away and its own king close by to escort it
+fn passed_pawn(board: &Board, params: &EvalParams, square: Mailbox64Index, color: Color, endgame: i32) -> i32 {
+    let rank = square.rank().relative(color).index() as usize;
+    let opponent = color.opposite();
+    // No passer bonus for a pawn on its last rank, which has nowhere to go
+    let Some(ahead_rank) = square.rank().offset(forward(color)) else {
+        return 0;
+    };
+    let ahead = Mailbox64Index::from_file_rank(square.file(), ahead_rank);
+    let (Some(own_king), Some(opponent_king)) = (board.king_square(color), board.king_square(opponent)) else {
+        return params.passed_pawn[rank];
+    };
+    let blocked = board.piece_at(ahead).is_some_and(|(_, piece_color)| piece_color == opponent);
+    let mut bonus = distance(opponent_king, ahead) * params.passed_pawn_king_distance[rank] / (1 + blocked as i32)
+        - distance(own_king, ahead) * params.passed_pawn_own_king_distance[rank];
+    // Rule of the square: with only pawns to stop it, the pawn queens when the opponent king
+    // cannot reach the promotion square in time, a move sooner when the opponent is to move
+    let only_pawns = board.pieces(opponent).all(|other| matches!(board.piece_at(other), Some((Piece::Pawn | Piece::King, _))));
+    let promotion = Mailbox64Index::from_file_rank(square.file(), Rank::Eighth.relative(color));
+    let moves_to_promote = (7 - rank as i32).min(5);
+    let tempo = (board.turn() == opponent) as i32;
+    let path_clear = board.piece_at(ahead).is_none();
+    if only_pawns && path_clear && distance(opponent_king, promotion) - tempo > moves_to_promote {
+        bonus += params.unstoppable_pawn;
+    }
+    params.passed_pawn[rank] + bonus * endgame / ENDGAME_SCALE
+}
+
+/// Whether a pawn of `color` attacks `square`, from diagonally behind it
+fn attacked_by_pawn(board: &Board, square: Mailbox64Index, color: Color) -> bool {
+    pawn_attacks(square, color.opposite()) & board.bitboard(Piece::Pawn, color) != 0
+}
+
+/// Moves a king needs to go from `from` to `to` on an empty board
+fn distance(from: Mailbox64Index, to: Mailbox64Index) -> i32 {
+    let files = (from.file().index() as i32 - to.file().index() as i32).abs();
+    let ranks = (from.rank().index() as i32 - to.rank().index() as i32).abs();
+    files.max(ranks)
+}
+
+/// Direction pawns of `color` move in, in ranks
+fn forward(color: Color) -> i8 {
+    match color {
+        Color::White => 1,
+        Color::Black => -1,
+    }
+}
+
+/// Whether `color` has a pawn on `file`
+fn has_pawn_on_file(board: &Board, color: Color, file: File) -> bool {
+    board.bitboard(Piece::Pawn, color) & file.bitboard() != 0
+}
+
+/// Whether `first` and `second` share a rank or file with nothing between them
+fn sees_along_line(board: &Board, first: Mailbox64Index, second: Mailbox64Index) -> bool {
+    let (low, high) = (first.0.min(second.0), first.0.max(second.0));
+    let step = if first.rank() == second.rank() {
+        1
+    } else if first.file() == second.file() {
+        8
+    } else {
+        return false;
+    };
+    (low + step..high).step_by(step as usize).all(|index| board.piece_at(Mailbox64Index(index)).is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: EvalParams = EvalParams::DEFAULT;
+
+    #[test]
+    fn material_from_the_side_to_move() {
+        assert_eq!(evaluate(&Board::default()), PARAMS.tempo);
+        // White is a queen up, which is good for White and as bad for Black, less the tempo
+        // of the side to move
+        let white_to_move = Board::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let black_to_move = Board::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_eq!(evaluate(&white_to_move), 900 + PARAMS.tempo);
+        assert_eq!(evaluate(&black_to_move), -900 + PARAMS.tempo);
+    }
+
+    /// `fen` with the board flipped top to bottom and the colors swapped
+    fn mirrored(fen: &str) -> Board {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| text.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect::<String>();
+        let placement = swap_case(&fields[0].split('/').rev().collect::<Vec<_>>().join("/"));
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+        castling.sort_by_key(|c| c.is_ascii_lowercase());
+        let castling: String = castling.into_iter().collect();
+        let en_passant: String = fields[3].chars().map(|c| match c { '3' => '6', '6' => '3', c => c }).collect();
+        Board::from_fen(&[&placement, turn, &castling, &en_passant, fields[4], fields[5]].join(" ")).unwrap()
+    }
+
+    #[test]
+    fn mirrored_positions_evaluate_alike() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "2r3k1/1q1nbppp/r3p3/3pP3/pPpP4/P1Q2N2/2RN1PPP/2R4K b - - 0 22",
+            "r1b2rk1/2q1b1pp/p2ppn2/1p6/3QP3/1BN1B3/PPP3PP/R4RK1 w - - 0 14",
+            "4k3/p7/8/3N4/4P3/8/8/4K3 w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "8/8/1P6/8/8/8/6k1/K7 b - - 0 1",
+            "4k3/8/3b4/8/2PP4/8/4B3/4K3 w - - 0 1",
+            "r5k1/5pp1/8/8/8/8/5PPP/R5K1 b - - 0 1",
+            "k7/8/2K5/8/8/8/8/7Q w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(evaluate(&board), evaluate(&mirrored(fen)), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn breakdown_adds_up() {
+        let board = Board::from_fen("2b1kb2/8/3p4/4p3/4P3/8/8/2B1KB2 b - - 0 1").unwrap();
+        let breakdown = evaluate_terms(&board, &PARAMS);
+        assert_eq!(breakdown.total(), evaluate(&board));
+        assert_eq!(breakdown.difference(Term::Material), -100);
+        assert!(breakdown.to_string().ends_with(&(-evaluate(&board)).to_string()));
+    }
+
+    #[test]
+    fn drawish_endings_are_scaled_down() {
+        let unscaled = EvalParams { opposite_bishops_scale: FULL_SCALE, rook_ending_scale: FULL_SCALE, ..PARAMS };
+        // Two pawns up with bishops of opposite colors, and of the same color
+        let opposite = Board::from_fen("4k3/8/3b4/8/2PP4/8/4B3/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate(&opposite), evaluate_with(&opposite, &unscaled) * PARAMS.opposite_bishops_scale / FULL_SCALE);
+        let same = Board::from_fen("4k3/3b4/8/8/2PP4/8/4B3/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate(&same), evaluate_with(&same, &unscaled));
+        // A rook ending a pawn up with every pawn on the kingside, and with pawns on both wings
+        let one_wing = Board::from_fen("r5k1/5pp1/8/8/8/8/5PPP/R5K1 b - - 0 1").unwrap();
+        assert_eq!(evaluate(&one_wing), evaluate_with(&one_wing, &unscaled) * PARAMS.rook_ending_scale / FULL_SCALE);
+        assert_eq!(evaluate_terms(&one_wing, &PARAMS).total(), evaluate(&one_wing));
+        let both_wings = Board::from_fen("r5k1/p4pp1/8/8/8/8/P4PPP/R5K1 b - - 0 1").unwrap();
+        assert_eq!(evaluate(&both_wings), evaluate_with(&both_wings, &unscaled));
+    }
+
+    #[test]
+    fn bishop_pair_and_bad_bishops() {
+        // Both sides have two bishops, but White's light-squared one has the blocked e4 pawn in
+        // its way, and Black's dark-squared one the blocked e5 pawn and the d6 pawn
+        let board = Board::from_fen("2b1kb2/8/3p4/4p3/4P3/8/8/2B1KB2 w - - 0 1").unwrap();
+        let white = 100 + PARAMS.bishop_pair - 2 * PARAMS.bad_bishop_pawn;
+        let black = 200 + PARAMS.bishop_pair - 2 * PARAMS.bad_bishop_pawn - PARAMS.bad_bishop_pawn;
+        assert_eq!(evaluate(&board), white - black + PARAMS.tempo);
+    }
+
+    #[test]
+    fn knight_outposts() {
+        // The knight on d5 is defended by the e4 pawn and out of reach of Black's pawns, which
+        // the one on b5 is not, as the a7 pawn can still drive it away
+        let outpost = Board::from_fen("4k3/p7/8/3N4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let base = Board::from_fen("4k3/p7/8/8/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate(&outpost) - evaluate(&base), 320 + PARAMS.knight_outpost + PARAMS.knight_outpost_strong);
+        let attackable = Board::from_fen("4k3/p7/8/1N6/2P5/8/8/4K3 w - - 0 1").unwrap();
+        let base = Board::from_fen("4k3/p7/8/8/2P5/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate(&attackable) - evaluate(&base), 320);
+        // The same knight and pawn a rank back are still in White's own half
+        let own_half = Board::from_fen("4k3/8/8/8/3N4/4P3/8/4K3 w - - 0 1").unwrap();
+        let base = Board::from_fen("4k3/8/8/8/8/4P3/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate(&own_half) - evaluate(&base), 320);
+    }
+
+    #[test]
+    fn passed_pawns_in_the_endgame() {
+        // A pawn on the sixth outside the square of the opponent king runs through, while
+        // inside it the king catches it
+        let runs = Board::from_fen("8/8/1P6/8/8/8/6k1/K7 w - - 0 1").unwrap();
+        let caught = Board::from_fen("8/3k4/1P6/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert!(evaluate(&runs) > 100 + PARAMS.unstoppable_pawn);
+        assert!(evaluate(&caught) < 100 + PARAMS.unstoppable_pawn);
+        // With all the pieces on the board the kings do not matter yet
+        assert_eq!(endgame(&Board::default()), 0);
+        let b6 = Mailbox64Index::try_from("b6").unwrap();
+        assert!(is_passed(&runs, b6, Color::White));
+        assert_eq!(passed_pawn(&runs, &PARAMS, b6, Color::White, 0), PARAMS.passed_pawn[5]);
+        // With no square ahead of it a pawn gets no bonus, rather than a panic
+        let b8 = Mailbox64Index::try_from("b8").unwrap();
+        assert_eq!(passed_pawn(&runs, &PARAMS, b8, Color::White, ENDGAME_SCALE), 0);
+        // Only an opponent blocker halves what the far opponent king is worth to the pawn
+        let own_blocker = Board::from_fen("8/1N6/1P6/8/8/8/6k1/K7 w - - 0 1").unwrap();
+        let opponent_blocker = Board::from_fen("8/1n6/1P6/8/8/8/6k1/K7 w - - 0 1").unwrap();
+        let own_king_penalty = 6 * PARAMS.passed_pawn_own_king_distance[5];
+        assert_eq!(passed_pawn(&own_blocker, &PARAMS, b6, Color::White, ENDGAME_SCALE), PARAMS.passed_pawn[5] + 5 * PARAMS.passed_pawn_king_distance[5] - own_king_penalty);
+        assert_eq!(passed_pawn(&opponent_blocker, &PARAMS, b6, Color::White, ENDGAME_SCALE), PARAMS.passed_pawn[5] + 5 * PARAMS.passed_pawn_king_distance[5] / 2 - own_king_penalty);
+    }
+
+    #[test]
+    fn space_behind_the_central_pawns() {
+        // White's two knights have f5 behind the f6 pawn in Black's half, but nothing behind the
+        // d5 and e4 pawns, which do not reach past it. Black has no minor pieces at all
+        let board = Board::from_fen("4k3/8/5P2/2pP4/4P3/2N2N2/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(space(&board, &PARAMS, Color::White), 2 * PARAMS.space);
+        assert_eq!(space(&board, &PARAMS, Color::Black), 0);
+        // The one square e5 in Black's half outweighs all eight behind a pawn front in White's
+        let own_half = Board::from_fen("4k3/8/8/8/2PPPP2/2N2N2/8/4K3 w - - 0 1").unwrap();
+        let opponent_half = Board::from_fen("4k3/8/4P3/8/2PP1P2/2N2N2/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(space(&own_half, &PARAMS, Color::White), 0);
+        assert_eq!(space(&opponent_half, &PARAMS, Color::White), 2 * PARAMS.space);
+        // It only counts in the middlegame
+        assert_eq!(evaluate_terms(&board, &PARAMS).difference(Term::Space), 0);
+    }
+
+    #[test]
+    fn mop_up_drives_the_bare_king_to_the_corner() {
+        let cornered = Board::from_fen("k7/8/2K5/8/8/8/8/7Q w - - 0 1").unwrap();
+        let centered = Board::from_fen("8/8/8/3k4/8/8/8/K6Q w - - 0 1").unwrap();
+        assert_eq!(evaluate(&cornered), 900 + 6 * PARAMS.mop_up_edge + 5 * PARAMS.mop_up_kings + PARAMS.tempo);
+        assert_eq!(evaluate(&centered), 900 + 3 * PARAMS.mop_up_kings + PARAMS.tempo);
+        assert_eq!(evaluate(&Board::from_fen("k7/8/2K5/8/8/8/8/7Q b - - 0 1").unwrap()), -evaluate(&cornered) + 2 * PARAMS.tempo);
+    }
+
+    #[test]
+    fn rooks_on_open_files_and_the_seventh() {
+        // White's rooks are on the open d-file, connected, and one of them on the seventh
+        let board = Board::from_fen("4k3/pppR1ppp/8/8/8/8/PPP2PPP/3RK3 w - - 0 1").unwrap();
+        let base = Board::from_fen("4k3/ppp2ppp/8/8/8/8/PPP2PPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate(&board) - evaluate(&base), 2 * (500 + PARAMS.rook_open_file) + PARAMS.rook_on_seventh + PARAMS.connected_rooks);
+    }
+}
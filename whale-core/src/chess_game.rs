@@ -0,0 +1,689 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::chess_parts::*;
+use crate::chess_engine::*;
+use crate::chess_notation::to_san;
+use crate::chess_eco::{classify, Opening};
+use crate::chess_clock::{Clock, TimeControl};
+
+/// Positions reached during a game, used to recognise repetitions
+#[derive(Clone)]
+pub struct History {
+    /// Repetition hash and halfmove clock of every position, starting with the initial one
+    positions: Vec<(u64, u8)>,
+}
+
+impl History {
+    /// History starting from `board`
+    pub fn new(board: &Board) -> History {
+        History {
+            positions: vec![(board.repetition_hash(), board.halfmove_clock())],
+        }
+    }
+
+    /// Record the position reached after a move
+    pub fn push(&mut self, board: &Board) {
+        self.positions.push((board.repetition_hash(), board.halfmove_clock()));
+    }
+
+    /// Forget the last recorded position, when its move is taken back
+    pub fn pop(&mut self) {
+        if self.positions.len() > 1 {
+            self.positions.pop();
+        }
+    }
+
+    /// How often the current position has occurred. Only positions since the last
+    /// capture or pawn move are compared, as nothing before it can repeat
+    pub fn repetition_count(&self) -> usize {
+        let (current, halfmove_clock) = *self.positions.last().unwrap();
+        self.positions
+            .iter()
+            .rev()
+            .take(halfmove_clock as usize + 1)
+            .filter(|(hash, _)| *hash == current)
+            .count()
+    }
+
+    /// Repetition hashes of the positions before the current one that it could still repeat,
+    /// those since the last capture or pawn move, oldest first
+    pub fn earlier_positions(&self) -> Vec<u64> {
+        let (_, halfmove_clock) = *self.positions.last().unwrap();
+        let start = self.positions.len().saturating_sub(halfmove_clock as usize + 1);
+        self.positions[start..self.positions.len() - 1].iter().map(|(hash, _)| *hash).collect()
+    }
+
+    /// Whether the current position has occurred three times, so a draw can be claimed
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+}
+
+/// How a decisive game was won
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum WinReason {
+    /// The loser's king is in check with no legal move
+    Checkmate,
+    /// The loser gave up
+    Resignation,
+    /// The loser ran out of time
+    Timeout,
+    /// Decided away from the board, e.g. a result read from a PGN whose last position is not mate
+    Adjudication,
+}
+
+/// Why a game ended in a draw
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum DrawReason {
+    /// The side to move is not in check but has no legal move
+    Stalemate,
+    /// The same position came up for the third time
+    ThreefoldRepetition,
+    /// Fifty moves by each side without a capture or a pawn move
+    FiftyMoveRule,
+    /// Neither side has enough pieces left to mate
+    InsufficientMaterial,
+    /// One side ran out of time, but the other has too little left to ever mate
+    TimeoutVsInsufficientMaterial,
+    /// One side offered a draw and the other accepted
+    Agreement,
+    /// Decided away from the board, e.g. a result read from a PGN
+    Adjudication,
+}
+
+/// Whether and how a game ended
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameResult {
+    /// Still being played
+    Ongoing,
+    /// White won
+    WhiteWins(WinReason),
+    /// Black won
+    BlackWins(WinReason),
+    /// Nobody won
+    Draw(DrawReason),
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let win_reason = |reason: &WinReason| match reason {
+            WinReason::Checkmate => "checkmate",
+            WinReason::Resignation => "resignation",
+            WinReason::Timeout => "timeout",
+            WinReason::Adjudication => "adjudication",
+        };
+        match self {
+            GameResult::Ongoing => write!(f, "Game in progress"),
+            GameResult::WhiteWins(reason) => write!(f, "White wins by {}", win_reason(reason)),
+            GameResult::BlackWins(reason) => write!(f, "Black wins by {}", win_reason(reason)),
+            GameResult::Draw(reason) => write!(f, "Draw by {}", match reason {
+                DrawReason::Stalemate => "stalemate",
+                DrawReason::ThreefoldRepetition => "threefold repetition",
+                DrawReason::FiftyMoveRule => "the fifty-move rule",
+                DrawReason::InsufficientMaterial => "insufficient material",
+                DrawReason::TimeoutVsInsufficientMaterial => "timeout vs insufficient material",
+                DrawReason::Agreement => "agreement",
+                DrawReason::Adjudication => "adjudication",
+            }),
+        }
+    }
+}
+
+/// Result of the game in `board`, reached through the positions in `history`
+pub fn game_state(board: &Board, history: &History) -> GameResult {
+    if is_checkmate(board) {
+        return match board.turn() {
+            Color::White => GameResult::BlackWins(WinReason::Checkmate),
+            Color::Black => GameResult::WhiteWins(WinReason::Checkmate),
+        };
+    }
+    if is_stalemate(board) {
+        return GameResult::Draw(DrawReason::Stalemate);
+    }
+    if is_insufficient_material(board) {
+        return GameResult::Draw(DrawReason::InsufficientMaterial);
+    }
+    if board.halfmove_clock() >= 100 {
+        return GameResult::Draw(DrawReason::FiftyMoveRule);
+    }
+    if history.is_threefold_repetition() {
+        return GameResult::Draw(DrawReason::ThreefoldRepetition);
+    }
+    GameResult::Ongoing
+}
+
+/// Value, symbol and meaning of the numeric annotation glyphs that have a common symbol
+const NAG_SYMBOLS: &[(u8, &str, &str)] = &[
+    (1, "!", "good move"),
+    (2, "?", "mistake"),
+    (3, "!!", "brilliant move"),
+    (4, "??", "blunder"),
+    (5, "!?", "interesting move"),
+    (6, "?!", "dubious move"),
+    (7, "□", "only move"),
+    (10, "=", "equal position"),
+    (13, "∞", "unclear position"),
+    (14, "⩲", "White is slightly better"),
+    (15, "⩱", "Black is slightly better"),
+    (16, "±", "White is better"),
+    (17, "∓", "Black is better"),
+    (18, "+-", "White is winning"),
+    (19, "-+", "Black is winning"),
+    (22, "⨀", "White is in zugzwang"),
+    (23, "⨀", "Black is in zugzwang"),
+    (36, "↑", "White has the initiative"),
+    (37, "↑", "Black has the initiative"),
+    (40, "→", "White has the attack"),
+    (41, "→", "Black has the attack"),
+    (132, "⇆", "White has counterplay"),
+    (133, "⇆", "Black has counterplay"),
+    (138, "⊕", "White is in time trouble"),
+    (139, "⊕", "Black is in time trouble"),
+    (146, "N", "novelty"),
+];
+
+/// ASCII spellings of the glyphs written with other symbols in `NAG_SYMBOLS`
+const NAG_ALIASES: &[(&str, u8)] = &[("+=", 14), ("=+", 15), ("+/-", 16), ("-/+", 17)];
+
+/// A numeric annotation glyph, PGN's `$n`, judging a move or the position after it
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Nag(pub u8);
+
+impl Nag {
+    /// The glyph written as `symbol`, like "!?" or "+-", including the ASCII spellings of
+    /// the positional glyphs such as "+="
+    pub fn from_symbol(symbol: &str) -> Option<Nag> {
+        NAG_SYMBOLS
+            .iter()
+            .find(|(_, known, _)| *known == symbol)
+            .map(|&(value, _, _)| value)
+            .or_else(|| NAG_ALIASES.iter().find(|(known, _)| *known == symbol).map(|&(_, value)| value))
+            .map(Nag)
+    }
+
+    /// Common symbol of the glyph, if it has one
+    pub fn symbol(self) -> Option<&'static str> {
+        NAG_SYMBOLS.iter().find(|(value, _, _)| *value == self.0).map(|&(_, symbol, _)| symbol)
+    }
+
+    /// What the glyph means, in words
+    pub fn description(self) -> String {
+        match NAG_SYMBOLS.iter().find(|(value, _, _)| *value == self.0) {
+            Some((_, _, description)) => description.to_string(),
+            None => format!("annotation ${}", self.0),
+        }
+    }
+
+    /// Whether the glyph judges the move itself, like "!" or "??", rather than the position
+    pub fn is_move_assessment(self) -> bool {
+        (1..=6).contains(&self.0)
+    }
+}
+
+/// The symbol of the glyph, or `$n` when it has none
+impl fmt::Display for Nag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.symbol() {
+            Some(symbol) => write!(f, "{}", symbol),
+            None => write!(f, "${}", self.0),
+        }
+    }
+}
+
+/// A move in the tree of a game, with the annotations attached to it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MoveNode {
+    /// The move played
+    pub mv: Move,
+    /// The move in SAN, as worked out in the position before it when it was added
+    pub san: String,
+    /// Comment standing before the move, as at the start of a variation
+    pub comment_before: Option<String>,
+    /// Comment following the move
+    pub comment: Option<String>,
+    /// Numeric annotation glyphs, in the order given
+    pub nags: Vec<Nag>,
+    /// Replies to the move. The first one continues the line, the others are variations of it
+    pub children: Vec<MoveNode>,
+}
+
+impl MoveNode {
+    fn new(board: &Board, mv: Move) -> MoveNode {
+        MoveNode {
+            san: to_san(board, &mv),
+            mv,
+            comment_before: None,
+            comment: None,
+            nags: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// A game being played: the tree of moves tried from the start position, the line leading to
+/// the current position, and any decision the players made themselves. Serialized as a
+/// `SavedGame`, leaving out what replaying the path recomputes
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "SavedGame", from = "SavedGame")]
+pub struct Game {
+    /// Moves from the start position. The first one is the main line, the others are variations
+    tree: Vec<MoveNode>,
+    /// Index of the child taken at each ply to get from the start to the current position
+    path: Vec<usize>,
+    /// Moves of `path`, in order
+    moves: Vec<Move>,
+    /// Start position followed by the position after each move of `moves`
+    positions: Vec<Board>,
+    history: History,
+    /// Children taken by the moves `undo` stepped back over, most recent last, for `redo`
+    undone: Vec<usize>,
+    /// PGN tag pairs describing the game, like the players or the event, in the order they were set
+    tags: Vec<(String, String)>,
+    /// Result the players settled on regardless of the position, by resigning or agreeing a draw
+    agreed_result: Option<GameResult>,
+    /// Side whose draw offer is waiting for an answer
+    draw_offer: Option<Color>,
+    /// Time control of a timed game, with the clocks indexed by color
+    timing: Option<(TimeControl, [Clock; 2])>,
+    /// When the clock of the side to move started running
+    turn_started: Option<Instant>,
+}
+
+impl Game {
+    /// Game with no moves yet, starting from `board`
+    pub fn new(board: Board) -> Game {
+        Game {
+            history: History::new(&board),
+            tree: Vec::new(),
+            path: Vec::new(),
+            moves: Vec::new(),
+            positions: vec![board],
+            undone: Vec::new(),
+            tags: Vec::new(),
+            agreed_result: None,
+            draw_offer: None,
+            timing: None,
+            turn_started: None,
+        }
+    }
+
+    /// Chess960 game from start position `number`, see `chess960_fen`, tagged as such for PGN
+    pub fn chess960(number: u16) -> Option<Game> {
+        let mut game = Game::new(Board::from_fen(&chess960_fen(number)?).unwrap());
+        game.set_tag("Variant", "Chess960");
+        Some(game)
+    }
+
+    /// Chess960 game from a start position picked at random
+    pub fn random_chess960() -> Game {
+        // Hashers are seeded randomly for every process, which is random enough to pick a position
+        let number = RandomState::new().build_hasher().finish() % 960;
+        Game::chess960(number as u16).unwrap()
+    }
+
+    /// Current position
+    pub fn board(&self) -> &Board {
+        self.positions.last().unwrap()
+    }
+
+    /// Positions the game went through to the current one, for the repetition rule
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Position the game started from
+    pub fn start(&self) -> &Board {
+        &self.positions[0]
+    }
+
+    /// Moves leading from the start position to the current one
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Pieces of `color` captured on the way to the current position, in the order they fell
+    pub fn captured_pieces(&self, color: Color) -> Vec<Piece> {
+        // positions[i] is the position moves[i] was played in, so its turn is the capturing side
+        self.moves
+            .iter()
+            .zip(&self.positions)
+            .filter(|(_, board)| board.turn() != color)
+            .filter_map(|(mv, _)| mv.captured)
+            .collect()
+    }
+
+    /// Moves that can be played from the start position, with everything that follows them
+    pub fn tree(&self) -> &[MoveNode] {
+        &self.tree
+    }
+
+    /// Where the current position is in `tree`, see `go_to`
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    /// Node of the move that led to the current position, if any move has been played
+    pub fn current_node_mut(&mut self) -> Option<&mut MoveNode> {
+        let (last, parents) = self.path.split_last()?;
+        let mut nodes = &mut self.tree;
+        for &index in parents {
+            nodes = &mut nodes[index].children;
+        }
+        Some(&mut nodes[*last])
+    }
+
+    /// Moves that can follow the current position, main line first
+    fn current_children_mut(&mut self) -> &mut Vec<MoveNode> {
+        let mut nodes = &mut self.tree;
+        for &index in &self.path {
+            nodes = &mut nodes[index].children;
+        }
+        nodes
+    }
+
+    /// Jump to the position reached by taking child `path[0]` of the start position, then child
+    /// `path[1]` of that move and so on. Indices past the end of the tree are ignored
+    pub fn go_to(&mut self, path: &[usize]) {
+        self.positions.truncate(1);
+        self.history = History::new(&self.positions[0]);
+        self.path.clear();
+        self.moves.clear();
+        self.undone.clear();
+        self.draw_offer = None;
+        let mut nodes = &self.tree;
+        for &index in path {
+            let Some(node) = nodes.get(index) else {
+                break;
+            };
+            let mut board = self.positions.last().unwrap().clone();
+            board.make_move(&node.mv);
+            self.history.push(&board);
+            self.positions.push(board);
+            self.path.push(index);
+            self.moves.push(node.mv.clone());
+            nodes = &node.children;
+        }
+    }
+
+    /// Step back to the position before the last move, keeping the move in the tree so `redo`
+    /// can replay it. Returns whether there was a move to take back
+    pub fn undo(&mut self) -> bool {
+        let Some(index) = self.path.pop() else {
+            return false;
+        };
+        self.moves.pop();
+        self.positions.pop();
+        self.history.pop();
+        self.undone.push(index);
+        self.draw_offer = None;
+        true
+    }
+
+    /// Replay the move last taken back by `undo`, or follow the main line when nothing was
+    /// taken back. Returns whether there was a move to replay
+    pub fn redo(&mut self) -> bool {
+        let index = self.undone.pop().unwrap_or(0);
+        let Some(node) = self.current_children_mut().get(index) else {
+            return false;
+        };
+        let mv = node.mv.clone();
+        let undone = std::mem::take(&mut self.undone);
+        self.play(&mv);
+        self.undone = undone;
+        true
+    }
+
+    /// PGN tags as name and value, in the order they were set
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Value of the tag called `name`, if set
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Set the tag called `name`, replacing any previous value
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, current)) => *current = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Result in the current position, or the one agreed on or adjudicated, or a loss on time
+    pub fn result(&self) -> GameResult {
+        if let Some(result) = &self.agreed_result {
+            return result.clone();
+        }
+        if let Some(color) = [Color::White, Color::Black].into_iter().find(|&color| self.clock(color).is_some_and(Clock::is_flagged)) {
+            return timeout(self.board(), color);
+        }
+        let result = game_state(self.board(), &self.history);
+        let turn = self.board().turn();
+        if result == GameResult::Ongoing && self.remaining_time(turn) == Some(Duration::ZERO) {
+            return timeout(self.board(), turn);
+        }
+        result
+    }
+
+    /// Result at the end of the main line, whichever position is current
+    pub fn main_line_result(&self) -> GameResult {
+        if let Some(result) = &self.agreed_result {
+            return result.clone();
+        }
+        let mut board = self.start().clone();
+        let mut history = History::new(&board);
+        let mut nodes = &self.tree;
+        while let Some(node) = nodes.first() {
+            board.make_move(&node.mv);
+            history.push(&board);
+            nodes = &node.children;
+        }
+        game_state(&board, &history)
+    }
+
+    /// Opening of the moves leading to the current position
+    pub fn opening(&self) -> Option<Opening> {
+        classify(self.start(), &self.moves)
+    }
+
+    /// Opening of the main line, whichever position is current
+    pub fn main_line_opening(&self) -> Option<Opening> {
+        let mut moves = Vec::new();
+        let mut nodes = &self.tree;
+        while let Some(node) = nodes.first() {
+            moves.push(node.mv.clone());
+            nodes = &node.children;
+        }
+        classify(self.start(), &moves)
+    }
+
+    /// Play a move, following it in the tree if it was tried before and adding it as a new
+    /// variation otherwise. Replying with a move instead of accepting declines a pending draw
+    /// offer. In a timed game the mover is charged the time since their clock started
+    pub fn make_move(&mut self, mv: &Move) {
+        let elapsed = self.turn_started.map_or(Duration::ZERO, |started| started.elapsed());
+        self.make_timed_move(mv, elapsed);
+    }
+
+    /// Play a move like `make_move`, charging the mover `elapsed` in a timed game. Suits players
+    /// timing themselves, like an engine in a match
+    pub fn make_timed_move(&mut self, mv: &Move, elapsed: Duration) {
+        let turn = self.board().turn();
+        if let Some((control, clocks)) = &mut self.timing {
+            clocks[turn as usize].record_move(control, elapsed);
+            self.turn_started = Some(Instant::now());
+        }
+        self.play(mv);
+    }
+
+    /// Follow or add `mv` in the tree and make it the current position
+    fn play(&mut self, mv: &Move) {
+        let board = self.board().clone();
+        let children = self.current_children_mut();
+        let index = match children.iter().position(|node| node.mv == *mv) {
+            Some(index) => index,
+            None => {
+                children.push(MoveNode::new(&board, mv.clone()));
+                children.len() - 1
+            },
+        };
+        let mut board = self.board().clone();
+        board.make_move(mv);
+        self.history.push(&board);
+        self.positions.push(board);
+        self.path.push(index);
+        self.moves.push(mv.clone());
+        self.undone.clear();
+        if self.draw_offer == Some(self.board().turn()) {
+            self.draw_offer = None;
+        }
+    }
+
+    /// Record a result decided away from the board, such as one read from a PGN whose final
+    /// position is not over. Does nothing once the game has ended
+    pub fn adjudicate(&mut self, result: GameResult) {
+        if self.result() == GameResult::Ongoing && result != GameResult::Ongoing {
+            self.agreed_result = Some(result);
+        }
+    }
+
+    /// End the game with a win for the opponent of `color`
+    pub fn resign(&mut self, color: Color) {
+        if self.result() != GameResult::Ongoing {
+            return;
+        }
+        self.agreed_result = Some(match color {
+            Color::White => GameResult::BlackWins(WinReason::Resignation),
+            Color::Black => GameResult::WhiteWins(WinReason::Resignation),
+        });
+    }
+
+    /// Side with a draw offer on the table, if any
+    pub fn draw_offer(&self) -> Option<Color> {
+        self.draw_offer
+    }
+
+    /// Offer a draw to the opponent of `color`, if the game is still on and no offer is open
+    pub fn offer_draw(&mut self, color: Color) {
+        if self.result() == GameResult::Ongoing && self.draw_offer.is_none() {
+            self.draw_offer = Some(color);
+        }
+    }
+
+    /// Accept the opponent's draw offer, ending the game. Does nothing without one
+    pub fn accept_draw(&mut self, color: Color) {
+        if self.draw_offer.is_some() && self.draw_offer != Some(color) {
+            self.agreed_result = Some(GameResult::Draw(DrawReason::Agreement));
+            self.draw_offer = None;
+        }
+    }
+
+    /// Turn down the opponent's draw offer, so the game goes on
+    pub fn decline_draw(&mut self, color: Color) {
+        if self.draw_offer.is_some() && self.draw_offer != Some(color) {
+            self.draw_offer = None;
+        }
+    }
+
+    /// Play the game under `control` from now on, with both clocks full and the clock of the
+    /// side to move running. Also sets the `TimeControl` tag
+    pub fn set_time_control(&mut self, control: TimeControl) {
+        self.set_tag("TimeControl", &control.to_pgn());
+        let clock = Clock::new(&control);
+        self.timing = Some((control, [clock.clone(), clock]));
+        self.turn_started = Some(Instant::now());
+    }
+
+    /// Time control of a timed game
+    pub fn time_control(&self) -> Option<&TimeControl> {
+        self.timing.as_ref().map(|(control, _)| control)
+    }
+
+    /// Clock of `color` in a timed game, as it stood when their last move was charged
+    pub fn clock(&self, color: Color) -> Option<&Clock> {
+        self.timing.as_ref().map(|(_, clocks)| &clocks[color as usize])
+    }
+
+    /// Time `color` has left right now in a timed game, counting the running clock of the side
+    /// to move while the game is on
+    pub fn remaining_time(&self, color: Color) -> Option<Duration> {
+        let (control, clocks) = self.timing.as_ref()?;
+        let clock = &clocks[color as usize];
+        let running = color == self.board().turn()
+            && self.agreed_result.is_none()
+            && !clocks.iter().any(Clock::is_flagged)
+            && game_state(self.board(), &self.history) == GameResult::Ongoing;
+        match self.turn_started {
+            Some(started) if running => Some(clock.remaining_after(control, started.elapsed())),
+            _ => Some(clock.remaining()),
+        }
+    }
+}
+
+/// Result when `color` runs out of time in `board`: a loss, unless the opponent could never mate
+fn timeout(board: &Board, color: Color) -> GameResult {
+    let pieces = |color: Color| -> Vec<Piece> {
+        board.pieces(color).map(|square| board.piece_at(square).unwrap().0).filter(|&piece| piece != Piece::King).collect()
+    };
+    let opponent_pieces = pieces(color.opposite());
+    let opponent_can_mate = match opponent_pieces[..] {
+        [] => false,
+        [Piece::Knight] | [Piece::Bishop] => !pieces(color).is_empty(),
+        _ => true,
+    };
+    match (opponent_can_mate, color) {
+        (false, _) => GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial),
+        (true, Color::White) => GameResult::BlackWins(WinReason::Timeout),
+        (true, Color::Black) => GameResult::WhiteWins(WinReason::Timeout),
+    }
+}
+
+/// What a `Game` needs to be rebuilt: the moves it knows and where it stands among them
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    start: Board,
+    tree: Vec<MoveNode>,
+    path: Vec<usize>,
+    tags: Vec<(String, String)>,
+    agreed_result: Option<GameResult>,
+    draw_offer: Option<Color>,
+    #[serde(default)]
+    timing: Option<(TimeControl, [Clock; 2])>,
+}
+
+impl From<Game> for SavedGame {
+    fn from(game: Game) -> SavedGame {
+        let mut positions = game.positions;
+        positions.truncate(1);
+        SavedGame {
+            start: positions.pop().unwrap(),
+            tree: game.tree,
+            path: game.path,
+            tags: game.tags,
+            agreed_result: game.agreed_result,
+            draw_offer: game.draw_offer,
+            timing: game.timing,
+        }
+    }
+}
+
+impl From<SavedGame> for Game {
+    fn from(saved: SavedGame) -> Game {
+        let mut game = Game::new(saved.start);
+        game.tree = saved.tree;
+        game.go_to(&saved.path);
+        game.tags = saved.tags;
+        game.agreed_result = saved.agreed_result;
+        game.draw_offer = saved.draw_offer;
+        // The side to move starts thinking again when the game is restored
+        game.turn_started = saved.timing.is_some().then(Instant::now);
+        game.timing = saved.timing;
+        game
+    }
+}
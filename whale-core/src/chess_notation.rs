@@ -0,0 +1,611 @@
+use std::fmt;
+use crate::chess_parts::*;
+use crate::chess_engine::*;
+use crate::chess_game::*;
+
+/// The tags every PGN game has to carry, in the order they are written
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// The legal move `mv` in standard algebraic notation, for the position in `board` before it
+/// is played. The file or rank of the origin square is only added when another piece of the
+/// same kind could also reach the target
+pub fn to_san(board: &Board, mv: &Move) -> String {
+    let mut san = if mv.castle {
+        if mv.to.file() == File::G { "O-O".to_string() } else { "O-O-O".to_string() }
+    } else {
+        let mut san = if mv.piece == Piece::Pawn { String::new() } else { mv.piece.to_char().to_string() };
+        if mv.piece == Piece::Pawn {
+            if mv.captured.is_some() {
+                san.push(mv.from.file().to_char());
+            }
+        } else {
+            let rivals: Vec<_> = generate_all_legal_moves(board)
+                .into_iter()
+                .filter(|other| other.piece == mv.piece && other.to == mv.to && other.from != mv.from && !other.castle)
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|other| other.from.file() != mv.from.file()) {
+                    san.push(mv.from.file().to_char());
+                } else if rivals.iter().all(|other| other.from.rank() != mv.from.rank()) {
+                    san.push(mv.from.rank().to_char());
+                } else {
+                    san.push_str(&mv.from.to_string());
+                }
+            }
+        }
+        if mv.captured.is_some() {
+            san.push('x');
+        }
+        san.push_str(&mv.to.to_string());
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push(promotion.to_char());
+        }
+        san
+    };
+    if mv.gives_check {
+        let mut after = board.clone();
+        after.make_move(mv);
+        san.push(if has_legal_move(&after) { '+' } else { '#' });
+    }
+    san
+}
+
+/// Result marker closing the movetext, which doubles as the value of the Result tag
+fn result_marker(result: &GameResult) -> &'static str {
+    match result {
+        GameResult::Ongoing => "*",
+        GameResult::WhiteWins(_) => "1-0",
+        GameResult::BlackWins(_) => "0-1",
+        GameResult::Draw(_) => "1/2-1/2",
+    }
+}
+
+/// Serialize `game` as PGN: the seven tag roster, with "?" for what the game does not know,
+/// any other tags of the game, ECO and Opening tags classifying the main line unless the game
+/// sets them, then the movetext in SAN with move numbers, comments, NAGs and variations,
+/// closed by the result of the main line
+pub fn write_pgn(game: &Game) -> String {
+    let result = result_marker(&game.main_line_result());
+    let mut pgn = String::new();
+    for name in SEVEN_TAG_ROSTER {
+        let value = match name {
+            "Result" => result,
+            "Date" => game.tag(name).unwrap_or("????.??.??"),
+            _ => game.tag(name).unwrap_or("?"),
+        };
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+    }
+    let start = game.start();
+    if start.to_fen() != Board::default().to_fen() {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{}\"]\n", start.to_fen()));
+    }
+    for (name, value) in game.tags() {
+        if !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+        }
+    }
+    if let Some(opening) = game.main_line_opening() {
+        if game.tag("ECO").is_none() {
+            pgn.push_str(&format!("[ECO \"{}\"]\n", opening.eco));
+        }
+        if game.tag("Opening").is_none() {
+            pgn.push_str(&format!("[Opening \"{}\"]\n", escape_tag_value(opening.name)));
+        }
+    }
+    pgn.push('\n');
+
+    let mut tokens = Vec::new();
+    write_line(start, game.tree(), true, &mut tokens);
+    tokens.push(result.to_string());
+
+    // Export format keeps movetext lines below 80 characters
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > 79 {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        // Parentheses hug the moves of the variation they enclose
+        if !line.is_empty() && token != ")" && !line.ends_with('(') {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+/// Append the movetext for `nodes`, the moves playable in `board`, and everything after them.
+/// The first node continues the line and the others are written as variations right after it.
+/// Black moves get a move number when they open the line or follow a comment or variation
+fn write_line(board: &Board, nodes: &[MoveNode], mut number_black: bool, tokens: &mut Vec<String>) {
+    let mut board = board.clone();
+    let mut nodes = nodes;
+    while let Some(main) = nodes.first() {
+        number_black = write_node(&board, main, number_black, tokens);
+        for variation in &nodes[1..] {
+            tokens.push("(".to_string());
+            let number_black = write_node(&board, variation, true, tokens);
+            let mut after = board.clone();
+            after.make_move(&variation.mv);
+            write_line(&after, &variation.children, number_black, tokens);
+            tokens.push(")".to_string());
+        }
+        if nodes.len() > 1 {
+            number_black = true;
+        }
+        board.make_move(&main.mv);
+        nodes = &main.children;
+    }
+}
+
+/// Append one move with its number and annotations, returning whether a Black move right
+/// after it needs its number repeated
+fn write_node(board: &Board, node: &MoveNode, mut number_black: bool, tokens: &mut Vec<String>) -> bool {
+    if let Some(comment) = &node.comment_before {
+        tokens.push(format!("{{{}}}", comment));
+        number_black = true;
+    }
+    if board.turn() == Color::White {
+        tokens.push(format!("{}.", board.fullmove_clock()));
+    } else if number_black {
+        tokens.push(format!("{}...", board.fullmove_clock()));
+    }
+    tokens.push(node.san.clone());
+    for nag in &node.nags {
+        tokens.push(format!("${}", nag.0));
+    }
+    match &node.comment {
+        Some(comment) => {
+            tokens.push(format!("{{{}}}", comment));
+            true
+        },
+        None => false,
+    }
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Why a PGN could not be read
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgnError {
+    /// A line of the tag section that is not a `[Name "value"]` pair
+    Tag(String),
+    /// The FEN tag does not describe a valid position
+    Fen(FenError),
+    /// A move of the movetext that is not legal, or not a move at all, in the position reached
+    /// by the moves before it
+    IllegalMove {
+        /// Move number as written in PGN, like "12..."
+        number: String,
+        /// The move as written
+        san: String,
+    },
+    /// A `{` comment that is never closed
+    UnterminatedComment,
+    /// A `(` variation that is never closed, or a `)` without one
+    UnbalancedVariation,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::Tag(line) => write!(f, "invalid tag pair '{}'", line),
+            PgnError::Fen(error) => write!(f, "invalid FEN tag: {}", error),
+            PgnError::IllegalMove { number, san } => write!(f, "illegal move {} {}", number, san),
+            PgnError::UnterminatedComment => write!(f, "unterminated comment"),
+            PgnError::UnbalancedVariation => write!(f, "unbalanced variation parentheses"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// The legal move written as `san` in `board`. Check and annotation suffixes are ignored, as
+/// are a missing `=` before the promotion piece and castling written with zeros
+pub fn from_san(board: &Board, san: &str) -> Option<Move> {
+    let normalize = |text: &str| text.trim_end_matches(['+', '#', '!', '?']).replace('=', "").replace('0', "O");
+    let wanted = normalize(san);
+    generate_all_legal_moves(board)
+        .into_iter()
+        .find(|mv| normalize(&to_san(board, mv)) == wanted)
+}
+
+/// Pieces of PGN movetext
+enum MovetextToken {
+    Move(String),
+    Comment(String),
+    Nag(Nag),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+fn tokenize_movetext(movetext: &str) -> Result<Vec<MovetextToken>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = movetext.chars().peekable();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(PgnError::UnterminatedComment),
+                    }
+                }
+                tokens.push(MovetextToken::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" ")));
+            },
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push(MovetextToken::Comment(comment.trim().to_string()));
+            },
+            '(' => {
+                depth += 1;
+                tokens.push(MovetextToken::VariationStart);
+            },
+            ')' => {
+                if depth == 0 {
+                    return Err(PgnError::UnbalancedVariation);
+                }
+                depth -= 1;
+                tokens.push(MovetextToken::VariationEnd);
+            },
+            '$' => {
+                let mut nag = String::new();
+                while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+                    nag.push(digit);
+                }
+                tokens.push(MovetextToken::Nag(Nag(nag.parse().unwrap_or(0))));
+            },
+            c if c.is_whitespace() => (),
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"{};()$".contains(*c)) {
+                    word.push(c);
+                }
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&word.as_str()) {
+                    tokens.push(MovetextToken::Result(word));
+                    continue;
+                }
+                if let Some(nag) = Nag::from_symbol(&word) {
+                    tokens.push(MovetextToken::Nag(nag));
+                    continue;
+                }
+                // Move numbers may be glued to the move after them, as in "12.e4", and
+                // annotations follow it, as in "e4!?"
+                let san = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                let (san, suffix) = san.split_at(san.trim_end_matches(['!', '?']).len());
+                if !san.is_empty() {
+                    tokens.push(MovetextToken::Move(san.to_string()));
+                }
+                if let Some(nag) = Nag::from_symbol(suffix) {
+                    tokens.push(MovetextToken::Nag(nag));
+                }
+            },
+        }
+    }
+    if depth != 0 {
+        return Err(PgnError::UnbalancedVariation);
+    }
+    Ok(tokens)
+}
+
+/// Parse a `[Name "value"]` tag pair line
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, quoted) = inner.split_once(char::is_whitespace)?;
+    let quoted = quoted.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        value.push(if c == '\\' { chars.next()? } else { c });
+    }
+    Some((name.to_string(), value))
+}
+
+/// Read the first game of a PGN, replaying its moves and variations through the legal move
+/// generator. Tags, comments and NAGs are kept on the game, and a result the final position
+/// does not explain is recorded as adjudicated. The game is left at the end of the main line
+pub fn read_pgn(pgn: &str) -> Result<Game, PgnError> {
+    let mut tags = Vec::new();
+    let mut movetext = String::new();
+    let mut in_tags = true;
+    for line in pgn.lines() {
+        let trimmed = line.trim();
+        if in_tags && trimmed.is_empty() {
+            continue;
+        }
+        if in_tags && trimmed.starts_with('[') {
+            tags.push(parse_tag(trimmed).ok_or_else(|| PgnError::Tag(trimmed.to_string()))?);
+            continue;
+        }
+        in_tags = false;
+        movetext.push_str(line);
+        movetext.push('\n');
+    }
+
+    let start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Board::from_fen(fen).map_err(PgnError::Fen)?,
+        None => Board::default(),
+    };
+    let mut game = Game::new(start);
+    for (name, value) in &tags {
+        game.set_tag(name, value);
+    }
+
+    // Positions to come back to when the variations opened so far end
+    let mut variation_starts: Vec<Vec<usize>> = Vec::new();
+    // A comment is attached to the move before it, unless it opens the game or a variation
+    let mut moved_in_line = false;
+    let mut pending_comment: Option<String> = None;
+    for token in tokenize_movetext(&movetext)? {
+        match token {
+            MovetextToken::VariationStart => {
+                // A variation replaces the move just played, so it starts from the position before it
+                let Some((_, parent)) = game.path().split_last() else {
+                    return Err(PgnError::UnbalancedVariation);
+                };
+                let parent = parent.to_vec();
+                variation_starts.push(game.path().to_vec());
+                game.go_to(&parent);
+                moved_in_line = false;
+            },
+            MovetextToken::VariationEnd => {
+                let path = variation_starts.pop().unwrap();
+                game.go_to(&path);
+                moved_in_line = true;
+            },
+            MovetextToken::Move(san) => {
+                let board = game.board();
+                let Some(mv) = from_san(board, &san) else {
+                    let dots = if board.turn() == Color::White { "." } else { "..." };
+                    return Err(PgnError::IllegalMove { number: format!("{}{}", board.fullmove_clock(), dots), san });
+                };
+                game.make_move(&mv);
+                if let Some(comment) = pending_comment.take() {
+                    game.current_node_mut().unwrap().comment_before = Some(comment);
+                }
+                moved_in_line = true;
+            },
+            MovetextToken::Comment(comment) => {
+                let target = match game.current_node_mut() {
+                    Some(node) if moved_in_line => &mut node.comment,
+                    _ => &mut pending_comment,
+                };
+                *target = Some(match target.take() {
+                    Some(previous) => format!("{} {}", previous, comment),
+                    None => comment,
+                });
+            },
+            MovetextToken::Nag(nag) => {
+                if let Some(node) = game.current_node_mut() {
+                    node.nags.push(nag);
+                }
+            },
+            MovetextToken::Result(result) => {
+                if !variation_starts.is_empty() {
+                    return Err(PgnError::UnbalancedVariation);
+                }
+                match result.as_str() {
+                    "1-0" => game.adjudicate(GameResult::WhiteWins(WinReason::Adjudication)),
+                    "0-1" => game.adjudicate(GameResult::BlackWins(WinReason::Adjudication)),
+                    "1/2-1/2" => game.adjudicate(GameResult::Draw(DrawReason::Adjudication)),
+                    _ => (),
+                }
+                break;
+            },
+        }
+    }
+    Ok(game)
+}
+
+/// An EPD record: a position without move clocks, followed by operations describing it
+pub struct Epd {
+    /// The position, with the clocks taken from the `hmvc` and `fmvn` operations when given
+    pub board: Board,
+    /// Every operation in the order written, as the opcode and its operands without quotes
+    pub operations: Vec<(String, Vec<String>)>,
+    /// `id`: name of the record within its suite
+    pub id: Option<String>,
+    /// `bm`: best moves, one of which an engine is expected to find
+    pub best_moves: Vec<Move>,
+    /// `am`: moves to avoid
+    pub avoid_moves: Vec<Move>,
+    /// `ce`: centipawn evaluation from the point of view of the side to move
+    pub centipawn_evaluation: Option<i32>,
+}
+
+impl Epd {
+    /// Operands of the first operation with the given opcode
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice())
+    }
+}
+
+/// Why an EPD record could not be read
+#[derive(Clone, Debug, PartialEq)]
+pub enum EpdError {
+    /// Fewer than the four position fields
+    FieldCount(usize),
+    /// The position fields do not form a valid position
+    Fen(FenError),
+    /// An operation with an unterminated string or missing its closing `;`
+    Operation(String),
+    /// A `bm` or `am` operand that is not a legal move in the position
+    IllegalMove {
+        /// The operation the move belongs to
+        opcode: String,
+        /// The move as written
+        san: String,
+    },
+    /// An operand that should be a number but is not, like that of `ce`
+    Number {
+        /// The operation the operand belongs to
+        opcode: String,
+        /// The operand as written
+        operand: String,
+    },
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdError::FieldCount(count) => write!(f, "expected at least 4 fields, found {}", count),
+            EpdError::Fen(error) => write!(f, "invalid position: {}", error),
+            EpdError::Operation(operation) => write!(f, "invalid operation '{}'", operation),
+            EpdError::IllegalMove { opcode, san } => write!(f, "illegal move {} in {}", san, opcode),
+            EpdError::Number { opcode, operand } => write!(f, "invalid number '{}' in {}", operand, opcode),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+/// Split the operations of an EPD record, each an opcode followed by operands and ended by `;`.
+/// Operands in double quotes may contain spaces and semicolons
+fn split_epd_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            ';' => {
+                if words.is_empty() {
+                    return Err(EpdError::Operation(";".to_string()));
+                }
+                let opcode = words.remove(0);
+                operations.push((opcode, std::mem::take(&mut words)));
+            },
+            '"' => {
+                let mut operand = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => operand.push(c),
+                        None => return Err(EpdError::Operation(format!("\"{}", operand))),
+                    }
+                }
+                words.push(operand);
+            },
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+                    word.push(c);
+                }
+                words.push(word);
+            },
+        }
+    }
+    if !words.is_empty() {
+        return Err(EpdError::Operation(words.join(" ")));
+    }
+    Ok(operations)
+}
+
+/// Read one EPD record, like the positions of the WAC or STS test suites. The `bm` and `am`
+/// moves are resolved against the position, so a record that names an illegal move is rejected
+pub fn read_epd(record: &str) -> Result<Epd, EpdError> {
+    let fields: Vec<_> = record.trim().splitn(5, char::is_whitespace).collect();
+    if fields.len() < 4 {
+        return Err(EpdError::FieldCount(fields.len()));
+    }
+    let operations = split_epd_operations(fields.get(4).copied().unwrap_or(""))?;
+    let number = |opcode: &str| -> Result<Option<i64>, EpdError> {
+        let Some((_, operands)) = operations.iter().find(|(name, _)| name == opcode) else {
+            return Ok(None);
+        };
+        let operand = operands.first().cloned().unwrap_or_default();
+        operand.parse().map(Some).map_err(|_| EpdError::Number { opcode: opcode.to_string(), operand })
+    };
+    let halfmove_clock = number("hmvc")?.unwrap_or(0);
+    let fullmove_clock = number("fmvn")?.unwrap_or(1);
+    let centipawn_evaluation = number("ce")?.map(|ce| ce as i32);
+    let fen = format!("{} {} {} {} {} {}", fields[0], fields[1], fields[2], fields[3], halfmove_clock, fullmove_clock);
+    let board = Board::from_fen(&fen).map_err(EpdError::Fen)?;
+
+    let moves = |opcode: &str| -> Result<Vec<Move>, EpdError> {
+        let mut moves = Vec::new();
+        for (_, operands) in operations.iter().filter(|(name, _)| name == opcode) {
+            for san in operands {
+                let mv = from_san(&board, san).ok_or_else(|| EpdError::IllegalMove { opcode: opcode.to_string(), san: san.clone() })?;
+                moves.push(mv);
+            }
+        }
+        Ok(moves)
+    };
+    let best_moves = moves("bm")?;
+    let avoid_moves = moves("am")?;
+    let id = operations.iter().find(|(name, _)| name == "id").and_then(|(_, operands)| operands.first().cloned());
+    Ok(Epd { board, operations, id, best_moves, avoid_moves, centipawn_evaluation })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgn_round_trip() {
+        let pgn = "[Event \"Casual game\"]\n[White \"Anderssen\"]\n[Black \"Kieseritzky\"]\n\n\
+            1. e4 e5 2. f4 exf4 3. Bc4 Qh4+ 4. Kf1 b5 {Bryan Countergambit} 5. Bxb5 Nf6 6. Nf3 Qh6\n\
+            7. d3 Nh5 8. Nh4 Qg5 (8... g6 {is also played} (8... Qg5) 9. Nf5) 9. Nf5 c6 10. g4 Nf6\n\
+            11. Rg1 cxb5 12. h4 Qg6 13. h5 Qg5 14. Qf3 Ng8 15. Bxf4 Qf6 16. Nc3 Bc5 17. Nd5 Qxb2\n\
+            18. Bd6 Bxg1 $4 ({Better is} 18... Qxa1+ 19. Ke2 Qb2) 19. e5 Qxa1+ 20. Ke2 Na6 21. Nxg7+\n\
+            Kd8 22. Qf6+ Nxf6 23. Be7# 1-0\n";
+        let game = read_pgn(pgn).unwrap();
+        assert_eq!(game.path().len(), 45);
+        assert!(game.result() == GameResult::WhiteWins(WinReason::Checkmate));
+        assert_eq!(game.tag("White"), Some("Anderssen"));
+        let written = write_pgn(&game);
+        assert!(written.contains("[Result \"1-0\"]"));
+        assert!(written.contains("8. Nh4 Qg5 (8... g6 {is also played} 9. Nf5) 9. Nf5"));
+        assert!(written.contains("18. Bd6 Bxg1 $4 ({Better is} 18... Qxa1+ 19. Ke2 Qb2) 19. e5"));
+        assert_eq!(write_pgn(&read_pgn(&written).unwrap()), written);
+    }
+
+    #[test]
+    fn pgn_annotations() {
+        let game = read_pgn("1. e4! e5?! 2. Nf3 += 2... Nc6 $146 3. Bb5 $200 *").unwrap();
+        let line: Vec<&MoveNode> = std::iter::successors(game.tree().first(), |node| node.children.first()).collect();
+        assert_eq!(line[0].nags, [Nag(1)]);
+        assert_eq!(line[1].nags, [Nag(6)]);
+        assert_eq!(line[2].nags, [Nag(14)]);
+        assert_eq!(line[3].nags[0].to_string(), "N");
+        assert_eq!(line[4].nags[0].to_string(), "$200");
+        assert_eq!(line[2].nags[0].description(), "White is slightly better");
+        let written = write_pgn(&game);
+        assert!(written.contains("1. e4 $1 e5 $6 2. Nf3 $14 Nc6 $146 3. Bb5 $200 *"));
+        assert_eq!(write_pgn(&read_pgn(&written).unwrap()), written);
+    }
+
+    #[test]
+    fn epd_operations() {
+        let epd = read_epd("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";").unwrap();
+        assert_eq!(epd.id.as_deref(), Some("WAC.001"));
+        assert_eq!(epd.best_moves.len(), 1);
+        assert_eq!(epd.best_moves[0].to_uci(), "g3g6");
+        assert_eq!(epd.board.fullmove_clock(), 1);
+
+        let epd = read_epd("8/8/8/8/8/8/8/K6k w - - am Kb1 Ka2; ce -5; hmvc 12;").unwrap();
+        assert_eq!(epd.avoid_moves.len(), 2);
+        assert_eq!(epd.centipawn_evaluation, Some(-5));
+        assert_eq!(epd.board.halfmove_clock(), 12);
+        assert_eq!(epd.operation("hmvc"), Some(&["12".to_string()][..]));
+        assert!(read_epd("8/8/8/8/8/8/8/K6k w - - bm Kc3;").is_err());
+    }
+
+    #[test]
+    fn pgn_illegal_move() {
+        let error = read_pgn("1. e4 e5 2. Ke3 *").err().unwrap();
+        assert_eq!(error, PgnError::IllegalMove { number: "2.".to_string(), san: "Ke3".to_string() });
+    }
+}
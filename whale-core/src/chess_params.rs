@@ -0,0 +1,109 @@
+use std::fmt;
+use crate::chess_eval::EvalParams;
+use crate::chess_search::SearchParams;
+
+/// Every weight of the evaluation and depth and margin of the search, each under a name by which
+/// it can be set at runtime, from a config file of lines like `rook_open_file 25`. Those are
+/// the lines `whale-tune` prints, so tuned weights can be played with without recompiling
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Params {
+    /// Weights of the evaluation
+    pub eval: EvalParams,
+    /// Depths and margins of the search
+    pub search: SearchParams,
+}
+
+impl Params {
+    /// Every parameter with its name, those of the evaluation first
+    pub fn weights_mut(&mut self) -> Vec<(String, &mut i32)> {
+        let mut weights = self.eval.weights_mut();
+        weights.extend(self.search.weights_mut());
+        weights
+    }
+
+    /// Value of the parameter called `name`, if there is one
+    pub fn get(&self, name: &str) -> Option<i32> {
+        self.clone().weights_mut().into_iter().find(|(other, _)| other == name).map(|(_, value)| *value)
+    }
+
+    /// Set the parameter called `name` to `value`
+    pub fn set(&mut self, name: &str, value: i32) -> Result<(), ParamsError> {
+        let (_, weight) = self.weights_mut().into_iter().find(|(other, _)| other == name).ok_or_else(|| ParamsError::UnknownName(name.to_string()))?;
+        *weight = value;
+        Ok(())
+    }
+
+    /// The default parameters with those `config` sets. Each line of `config` is a name and a
+    /// value separated by whitespace; blank lines and lines starting with `#` are skipped
+    pub fn from_config(config: &str) -> Result<Params, ParamsError> {
+        let mut params = Params::default();
+        for line in config.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let mut fields = line.split_whitespace();
+            let (Some(name), Some(value), None) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(ParamsError::Line(line.to_string()));
+            };
+            let value = value.parse().map_err(|_| ParamsError::Value { name: name.to_string(), value: value.to_string() })?;
+            params.set(name, value)?;
+        }
+        Ok(params)
+    }
+}
+
+/// One line per parameter with its name and value, which `Params::from_config` reads back
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.clone().weights_mut() {
+            writeln!(f, "{} {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why parameters could not be set
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamsError {
+    /// A name no parameter has
+    UnknownName(String),
+    /// A value that is not a whole number
+    Value {
+        /// The parameter the value is for
+        name: String,
+        /// The value as written
+        value: String,
+    },
+    /// A line of a config file that is not a name and a value
+    Line(String),
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamsError::UnknownName(name) => write!(f, "unknown parameter '{}'", name),
+            ParamsError::Value { name, value } => write!(f, "invalid value '{}' for {}", value, name),
+            ParamsError::Line(line) => write!(f, "expected a name and a value, found '{}'", line),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_round_trip() {
+        let mut params = Params::default();
+        params.set("rook_open_file", 30).unwrap();
+        params.set("futility_margins[2]", 450).unwrap();
+        assert_eq!(params.eval.rook_open_file, 30);
+        assert_eq!(params.search.futility_margins, [0, 200, 450]);
+        assert_eq!(Params::from_config(&params.to_string()), Ok(params.clone()));
+        let config = "# Tuned\n\nrook_open_file 30\n  futility_margins[2]   450\n";
+        assert_eq!(Params::from_config(config), Ok(params));
+        assert_eq!(Params::from_config("queen_value 950").unwrap().get("queen_value"), Some(950));
+        assert_eq!(Params::from_config("pawn_value"), Err(ParamsError::Line("pawn_value".to_string())));
+        assert_eq!(Params::from_config("pawn_value x"), Err(ParamsError::Value { name: "pawn_value".to_string(), value: "x".to_string() }));
+        assert_eq!(Params::from_config("king_value 0"), Err(ParamsError::UnknownName("king_value".to_string())));
+    }
+}
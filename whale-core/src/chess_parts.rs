@@ -0,0 +1,1602 @@
+use std::fmt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use crate::chess_engine::{generate_legal, legal_move};
+
+/// Serialized as its FEN, which covers everything a position holds
+#[derive(Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Board {
+    /// Piece on each square as `(piece << 1) | color`, or 0 when empty, from a8 to h1. Only
+    /// written through `set_cell`, which keeps `occupancy`, `bitboards`, `king_squares` and `key`
+    /// in step
+    pub(crate) cells: [u8; 64],
+    /// Squares of the pieces of each color, one bit per `Mailbox64Index`, indexed by `Color`
+    occupancy: [u64; 2],
+    /// Squares of each kind of piece of either color, indexed by `Piece`
+    bitboards: [u64; 7],
+    /// Square of the king of each color, indexed by `Color`
+    king_squares: [Option<Mailbox64Index>; 2],
+    /// Zobrist hash of the pieces, side to move and castling rights, updated along with them.
+    /// `zobrist` adds the en passant square
+    key: u64,
+    turn: Color,
+    /// File of the rook each castling right belongs to, see `castling_right`
+    castling_availability: [Option<File>; 4],
+    /// Square behind a pawn that just advanced two squares. `make_move` sets it after a double
+    /// push and clears it after any other move, so it only lives for a single ply
+    en_passant_target_square: Option<Mailbox64Index>,
+    halfmove_clock: u8,
+    fullmove_clock: usize,
+}
+
+/// ASCII diagram with White at the bottom, followed by the FEN of the position
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  +---+---+---+---+---+---+---+---+")?;
+        for rank in Rank::ALL.into_iter().rev() {
+            write!(f, "{} |", rank)?;
+            for file in File::ALL {
+                match self.cells[Mailbox64Index::from_file_rank(file, rank).0 as usize] {
+                    0 => write!(f, "   |")?,
+                    cell => write!(f, " {} |", fen_char(cell))?,
+                }
+            }
+            writeln!(f)?;
+            writeln!(f, "  +---+---+---+---+---+---+---+---+")?;
+        }
+        writeln!(f, "    a   b   c   d   e   f   g   h")?;
+        write!(f, "{}", self.to_fen())
+    }
+}
+
+impl From<Board> for String {
+    fn from(board: Board) -> String {
+        board.to_fen()
+    }
+}
+
+impl TryFrom<String> for Board {
+    type Error = FenError;
+
+    fn try_from(fen: String) -> Result<Board, FenError> {
+        Board::from_fen(&fen)
+    }
+}
+
+impl Clone for Board {
+    fn clone(&self) -> Self {
+        Board {
+            cells: self.cells,
+            occupancy: self.occupancy,
+            bitboards: self.bitboards,
+            king_squares: self.king_squares,
+            key: self.key,
+            turn: self.turn,
+            castling_availability: self.castling_availability,
+            en_passant_target_square: self.en_passant_target_square,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_clock: self.fullmove_clock,
+        }
+    }
+}
+
+/// What is wrong with a FEN string, by field
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    /// Not six space separated fields
+    FieldCount(usize),
+    /// Piece placement without eight ranks
+    RankCount(usize),
+    /// A rank of the piece placement that does not cover eight files
+    RankLength(String),
+    /// Unknown piece letter in the piece placement
+    Piece(char),
+    /// A pawn on the first or eighth rank
+    PawnOnBackRank(Mailbox64Index),
+    /// Side to move other than `w` or `b`
+    Turn(String),
+    /// Castling letter that is neither KQkq nor a rook file
+    Castling(char),
+    /// En passant target that is not `-` or a square on the third or sixth rank
+    EnPassant(String),
+    /// Halfmove clock that is not a number
+    HalfmoveClock(String),
+    /// Fullmove number that is not a number
+    FullmoveClock(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::FieldCount(count) => write!(f, "expected 6 fields, found {}", count),
+            FenError::RankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::RankLength(rank) => write!(f, "rank '{}' does not cover 8 files", rank),
+            FenError::Piece(c) => write!(f, "invalid piece char '{}'", c),
+            FenError::PawnOnBackRank(square) => write!(f, "pawns cannot stand on {}", square),
+            FenError::Turn(turn) => write!(f, "invalid side to move '{}'", turn),
+            FenError::Castling(c) => write!(f, "invalid castling char '{}'", c),
+            FenError::EnPassant(square) => write!(f, "invalid en passant square '{}'", square),
+            FenError::HalfmoveClock(clock) => write!(f, "invalid halfmove clock '{}'", clock),
+            FenError::FullmoveClock(clock) => write!(f, "invalid fullmove clock '{}'", clock),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Why an edit to a `Board` was refused
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditError {
+    /// A pawn placed on the first or eighth rank
+    PawnOnBackRank(Mailbox64Index),
+    /// A second king for a side that already has one
+    SecondKing(Color),
+    /// Nothing to move on the square
+    EmptySquare(Mailbox64Index),
+    /// A castling right without the king on its back rank and a rook of its color on the
+    /// given file, on the given side of it
+    Castling(Color, File),
+    /// An en passant target that does not lie behind a pawn of the side not to move that could
+    /// just have advanced two squares
+    EnPassant(Mailbox64Index),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::PawnOnBackRank(square) => write!(f, "pawns cannot stand on {}", square),
+            EditError::SecondKing(color) => write!(f, "{:?} already has a king", color),
+            EditError::EmptySquare(square) => write!(f, "no piece on {}", square),
+            EditError::Castling(color, file) => write!(f, "{:?} cannot castle with a rook on the {} file", color, file),
+            EditError::EnPassant(square) => write!(f, "{} is not an en passant target", square),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// Why `Board::from_moves` could not reach a position
+#[derive(Clone, Debug, PartialEq)]
+pub enum MovesError {
+    /// The start position is neither `startpos` nor a valid FEN
+    Fen(FenError),
+    /// A move that is not UCI notation for a legal move in the position reached before it
+    IllegalMove {
+        /// Index of the move in the list
+        index: usize,
+        /// The move as given
+        uci: String,
+    },
+}
+
+impl fmt::Display for MovesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovesError::Fen(error) => write!(f, "invalid start position: {}", error),
+            MovesError::IllegalMove { index, uci } => write!(f, "illegal move {} at index {}", uci, index),
+        }
+    }
+}
+
+impl std::error::Error for MovesError {}
+
+/// Kind of a piece, regardless of its color
+#[allow(missing_docs)]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum Piece {
+    Pawn = 1,
+    Bishop,
+    Rook,
+    Knight,
+    Queen,
+    King
+}
+
+impl TryFrom<u8> for Piece {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Piece::*;
+        match value {
+            val if val == Pawn as u8 => Ok(Pawn),
+            val if val == Bishop as u8 => Ok(Bishop),
+            val if val == Rook as u8 => Ok(Rook),
+            val if val == Knight as u8 => Ok(Knight),
+            val if val == Queen as u8 => Ok(Queen),
+            val if val == King as u8 => Ok(King),
+            _ => Err(())
+        }
+    }
+}
+
+impl Piece {
+    /// Material value in centipawns, 0 for the king which can never be traded
+    pub fn value(&self) -> i32 {
+        match self {
+            Piece::Pawn => 100,
+            Piece::Knight => 320,
+            Piece::Bishop => 330,
+            Piece::Rook => 500,
+            Piece::Queen => 900,
+            Piece::King => 0,
+        }
+    }
+
+    /// Whether the piece moves any number of squares along its lines until something blocks it
+    pub fn is_slider(&self) -> bool {
+        matches!(self, Piece::Bishop | Piece::Rook | Piece::Queen)
+    }
+
+    /// Upper case letter of the piece as in FEN and SAN, like 'N'
+    pub fn to_char(&self) -> char {
+        match self {
+            Piece::Pawn => 'P',
+            Piece::Knight => 'N',
+            Piece::Bishop => 'B',
+            Piece::Rook => 'R',
+            Piece::Queen => 'Q',
+            Piece::King => 'K',
+        }
+    }
+
+    /// Piece written as its letter in either case, like 'N' or 'n'
+    pub fn from_char(c: char) -> Option<Piece> {
+        [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]
+            .into_iter()
+            .find(|piece| piece.to_char() == c.to_ascii_uppercase())
+    }
+}
+
+/// Steps of each kind of piece as file and row offsets for `offset_index_2d`, and whether it
+/// slides along them, indexed by `Piece`. Pawn steps are forwards, which `pawn_forward` turns
+/// into a row offset for the pawn's color
+pub(crate) const MOVESETS: [(&[(i8, i8)], bool); 7] = [
+    (&[], false),
+    (&[(0, 1), (0, 2), (1, 1), (-1, 1)], false),
+    (&[(1, 1), (1, -1), (-1, -1), (-1, 1)], true),
+    (&[(0, 1), (1, 0), (0, -1), (-1, 0)], true),
+    (&[(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)], false),
+    (&[(0, 1), (1, 0), (0, -1), (-1, 0), (1, 1), (1, -1), (-1, -1), (-1, 1)], true),
+    (&[(0, 1), (1, 0), (0, -1), (-1, 0), (1, 1), (1, -1), (-1, -1), (-1, 1)], false),
+];
+
+/// Row offset of a step forwards for a pawn of `color`. Rows count down from the eighth rank,
+/// so White's pawns move up towards lower rows
+pub(crate) fn pawn_forward(color: Color) -> i8 {
+    match color {
+        Color::White => -1,
+        Color::Black => 1,
+    }
+}
+
+/// Random keys XORed together into `Board::zobrist`
+struct ZobristKeys {
+    /// Indexed by cell value, so by piece and color, then by square
+    pieces: [[u64; 64]; 14],
+    black_to_move: u64,
+    /// Indexed like `castling_availability`, then by the file of the castling rook
+    castling: [[u64; 8]; 4],
+    /// By file of the en passant target square
+    en_passant: [u64; 8],
+}
+
+/// Generated from a fixed seed, so hashes stay the same from one run to the next
+static ZOBRIST: Lazy<ZobristKeys> = Lazy::new(|| {
+    // splitmix64
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let mut keys = ZobristKeys {
+        pieces: [[0; 64]; 14],
+        black_to_move: 0,
+        castling: [[0; 8]; 4],
+        en_passant: [0; 8],
+    };
+    keys.pieces.iter_mut().flatten().for_each(|key| *key = next());
+    keys.black_to_move = next();
+    keys.castling.iter_mut().flatten().for_each(|key| *key = next());
+    keys.en_passant.iter_mut().for_each(|key| *key = next());
+    keys
+});
+
+/// Side a piece belongs to
+#[allow(missing_docs)]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum Color {
+    Black = 0,
+    White = 1
+}
+
+impl Color {
+    /// The other side
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+impl From<Color> for bool {
+    fn from(color: Color) -> bool {
+        color as u8 == 1
+    }
+}
+
+impl From<bool> for Color {
+    fn from(value: bool) -> Color {
+        if value { Color::White } else { Color::Black }
+    }
+}
+
+impl TryFrom<u8> for Color {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Color::*;
+        match value {
+            val if val == Black as u8 => Ok(Black),
+            val if val == White as u8 => Ok(White),
+            _ => Err(())
+        }
+    }
+}
+
+fn new_piece(piece: Piece, color: Color) -> u8 {
+    ((piece as u8) << 1) | (color as u8)
+}
+
+/// Split a cell of `Board::cells` into its piece and color. Panics on an empty cell
+pub fn piece_from_u8(input: u8) -> (Piece, Color) {
+    let piece = (input >> 1).try_into().unwrap();
+    let color = (input & 1).try_into().unwrap();
+    (piece, color)
+}
+
+/// FEN letter of a non-empty cell, upper case for White
+fn fen_char(cell: u8) -> char {
+    let (piece, color) = piece_from_u8(cell);
+    if color == Color::White { piece.to_char() } else { piece.to_char().to_ascii_lowercase() }
+}
+
+/// Start position number `number` of Chess960 as FEN, numbered from 0 to 959 as Scharnagl did,
+/// so 518 is the standard starting position. Castling is given as KQkq, which X-FEN reads as
+/// the rooks on either side of the king
+pub fn chess960_fen(number: u16) -> Option<String> {
+    if number >= 960 {
+        return None;
+    }
+    let mut back_rank = [None; 8];
+    let mut number = number as usize;
+    // Bishops go on a light then a dark square, then the queen, knights, and the rooks either
+    // side of the king take the last three empty squares
+    back_rank[number % 4 * 2 + 1] = Some(Piece::Bishop);
+    number /= 4;
+    back_rank[number % 4 * 2] = Some(Piece::Bishop);
+    number /= 4;
+    let empty = |back_rank: &[Option<Piece>; 8]| (0..8).filter(|&file| back_rank[file].is_none()).collect::<Vec<_>>();
+    back_rank[empty(&back_rank)[number % 6]] = Some(Piece::Queen);
+    number /= 6;
+    const KNIGHTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+    let (first, second) = KNIGHTS[number];
+    let files = empty(&back_rank);
+    back_rank[files[first]] = Some(Piece::Knight);
+    back_rank[files[second]] = Some(Piece::Knight);
+    for (file, piece) in empty(&back_rank).into_iter().zip([Piece::Rook, Piece::King, Piece::Rook]) {
+        back_rank[file] = Some(piece);
+    }
+    let white: String = back_rank.iter().map(|piece| piece.unwrap().to_char()).collect();
+    Some(format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1", white.to_ascii_lowercase(), white))
+}
+
+/// The standard starting position
+impl Default for Board {
+    fn default() -> Board {
+        Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap()
+    }
+}
+
+impl Board {
+    /// Build board from FEN notation
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let parts: Vec<_> = fen.split_whitespace().collect();
+        if parts.len() != 6 {
+            return Err(FenError::FieldCount(parts.len()));
+        }
+
+        let fen_board = parts[0];
+        let turn = match parts[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            x => return Err(FenError::Turn(x.to_string())),
+        };
+
+        let castling_availability = parts[2];
+        let en_passant_target_square = match parts[3] {
+            "-" => None,
+            x => match x.as_bytes() {
+                [b'a'..=b'h', b'3' | b'6'] => x.try_into().ok(),
+                _ => return Err(FenError::EnPassant(x.to_string())),
+            },
+        };
+
+        let halfmove_clock = parts[4].parse::<u8>().map_err(|_| FenError::HalfmoveClock(parts[4].to_string()))?;
+        let fullmove_clock = parts[5].parse::<usize>().map_err(|_| FenError::FullmoveClock(parts[5].to_string()))?;
+
+        let mut board = Board {
+            cells: [0; 64],
+            occupancy: [0; 2],
+            bitboards: [0; 7],
+            king_squares: [None; 2],
+            key: if turn == Color::Black { ZOBRIST.black_to_move } else { 0 },
+            turn,
+            castling_availability: [None; 4],
+            en_passant_target_square,
+            halfmove_clock,
+            fullmove_clock,
+        };
+
+        let ranks: Vec<_> = fen_board.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::RankCount(ranks.len()));
+        }
+        for (rank, rank_text) in Rank::ALL.into_iter().rev().zip(ranks) {
+            let mut file = 0;
+            for c in rank_text.chars() {
+                if c.is_ascii_digit() {
+                    file += c.to_digit(10).unwrap() as u8;
+                } else {
+                    let piece = Piece::from_char(c).ok_or(FenError::Piece(c))?;
+                    let Some(square_file) = File::from_index(file) else {
+                        return Err(FenError::RankLength(rank_text.to_string()));
+                    };
+                    let square = Mailbox64Index::from_file_rank(square_file, rank);
+                    if piece == Piece::Pawn && (rank == Rank::First || rank == Rank::Eighth) {
+                        return Err(FenError::PawnOnBackRank(square));
+                    }
+                    board.set_cell(square, new_piece(piece, if c.is_ascii_uppercase() { Color::White } else { Color::Black }));
+                    file += 1;
+                }
+            }
+            if file != 8 {
+                return Err(FenError::RankLength(rank_text.to_string()));
+            }
+        }
+
+        // Castling letters are read once the pieces are placed, as they refer to the rooks.
+        // Besides KQkq this takes the rook file letters of Shredder-FEN, as in "HAha"
+        if castling_availability != "-" {
+            for c in castling_availability.chars() {
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let (kingside, file) = match c.to_ascii_lowercase() {
+                    'k' => (true, board.outermost_rook_file(color, true).unwrap_or(File::H)),
+                    'q' => (false, board.outermost_rook_file(color, false).unwrap_or(File::A)),
+                    letter => {
+                        let file = File::from_char(letter).ok_or(FenError::Castling(c))?;
+                        // Without a king on the back rank the right is dropped below anyway
+                        let king_file = board.back_rank_file_of(color, Piece::King).unwrap_or(File::E);
+                        (file > king_file, file)
+                    },
+                };
+                board.set_castling_availability(castling_right(color, kingside), Some(file));
+            }
+        }
+        // Rights the king and rooks cannot back up, as in FENs written by hand, are dropped
+        // rather than left for move generation to trip over
+        board.drop_impossible_castling_rights();
+        Ok(board)
+    }
+
+    /// Position reached by playing `moves`, in UCI notation, from `start`, a FEN or `startpos`
+    /// for the standard starting position, as in the UCI `position` command
+    pub fn from_moves(start: &str, moves: &[&str]) -> Result<Board, MovesError> {
+        let mut board = match start {
+            "startpos" => Board::default(),
+            fen => Board::from_fen(fen).map_err(MovesError::Fen)?,
+        };
+        for (index, &uci) in moves.iter().enumerate() {
+            let illegal = || MovesError::IllegalMove { index, uci: uci.to_string() };
+            let mv = Move::from_uci(&board, uci).ok_or_else(illegal)?;
+            let mv = legal_move(&board, &mv).ok_or_else(illegal)?;
+            board.make_move(&mv);
+        }
+        Ok(board)
+    }
+
+    /// File of the first piece of the given kind and color on that color's back rank
+    fn back_rank_file_of(&self, color: Color, piece: Piece) -> Option<File> {
+        let back_rank = Rank::First.relative(color);
+        File::ALL.into_iter().find(|&file| {
+            let cell = self.cells[Mailbox64Index::from_file_rank(file, back_rank).0 as usize];
+            cell != 0 && piece_from_u8(cell) == (piece, color)
+        })
+    }
+
+    /// File of the rook of `color` furthest from its king on the given side, on the back rank.
+    /// That is the rook X-FEN means by the K and Q castling letters
+    fn outermost_rook_file(&self, color: Color, kingside: bool) -> Option<File> {
+        let back_rank = Rank::First.relative(color);
+        let king_file = self.back_rank_file_of(color, Piece::King)?;
+        let is_rook = |file: &File| {
+            let cell = self.cells[Mailbox64Index::from_file_rank(*file, back_rank).0 as usize];
+            cell != 0 && piece_from_u8(cell) == (Piece::Rook, color)
+        };
+        if kingside {
+            File::ALL.into_iter().rev().take_while(|&file| file > king_file).find(is_rook)
+        } else {
+            File::ALL.into_iter().take_while(|&file| file < king_file).find(is_rook)
+        }
+    }
+
+    /// Describe the position in FEN notation, the inverse of `Board::from_fen`
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in Rank::ALL.into_iter().rev() {
+            let mut empty = 0;
+            for file in File::ALL {
+                let cell = self.cells[Mailbox64Index::from_file_rank(file, rank).0 as usize];
+                if cell == 0 {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                fen.push(fen_char(cell));
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank != Rank::First {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.turn == Color::White { " w " } else { " b " });
+        // X-FEN: KQkq while the right belongs to the outermost rook, the rook's file otherwise
+        let mut castling = String::new();
+        for (color, kingside, symbol) in [(Color::White, true, 'K'), (Color::White, false, 'Q'), (Color::Black, true, 'k'), (Color::Black, false, 'q')] {
+            let Some(file) = self.castling_availability[castling_right(color, kingside)] else {
+                continue;
+            };
+            if self.outermost_rook_file(color, kingside) == Some(file) {
+                castling.push(symbol);
+            } else {
+                let letter = file.to_char();
+                castling.push(if color == Color::White { letter.to_ascii_uppercase() } else { letter });
+            }
+        }
+        fen.push_str(if castling.is_empty() { "-" } else { &castling });
+        match &self.en_passant_target_square {
+            Some(square) => fen.push_str(&format!(" {}", square)),
+            None => fen.push_str(" -"),
+        }
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_clock));
+        fen
+    }
+
+    /// Piece on each square as `(piece << 1) | color`, or 0 when empty, indexed by
+    /// `Mailbox64Index`. See `piece_from_u8`
+    pub fn cells(&self) -> &[u8; 64] {
+        &self.cells
+    }
+
+    /// Squares of the pieces of `color`, in index order
+    pub fn pieces(&self, color: Color) -> impl Iterator<Item = Mailbox64Index> {
+        squares(self.occupancy[color as usize])
+    }
+
+    /// Squares of the pieces of `color`, one bit per `Mailbox64Index`
+    pub fn occupancy(&self, color: Color) -> u64 {
+        self.occupancy[color as usize]
+    }
+
+    /// Squares of the pieces of both colors, one bit per `Mailbox64Index`
+    pub fn occupied(&self) -> u64 {
+        self.occupancy[0] | self.occupancy[1]
+    }
+
+    /// Squares of the pieces of kind `piece` and color `color`, one bit per `Mailbox64Index`
+    pub fn bitboard(&self, piece: Piece, color: Color) -> u64 {
+        self.bitboards[piece as usize] & self.occupancy[color as usize]
+    }
+
+    /// Square of the king of `color`, if it has one
+    pub fn king_square(&self, color: Color) -> Option<Mailbox64Index> {
+        self.king_squares[color as usize]
+    }
+
+    /// Put `cell` on `square`, updating the piece lists, king squares and hash to match
+    fn set_cell(&mut self, square: Mailbox64Index, cell: u8) {
+        let old = self.cells[square.0 as usize];
+        if old != 0 {
+            self.key ^= ZOBRIST.pieces[old as usize][square.0 as usize];
+            let color = (old & 1) as usize;
+            self.occupancy[color] &= !(1 << square.0);
+            self.bitboards[(old >> 1) as usize] &= !(1 << square.0);
+            if old >> 1 == Piece::King as u8 && self.king_squares[color] == Some(square) {
+                self.king_squares[color] = None;
+            }
+        }
+        if cell != 0 {
+            self.key ^= ZOBRIST.pieces[cell as usize][square.0 as usize];
+            let color = (cell & 1) as usize;
+            self.occupancy[color] |= 1 << square.0;
+            self.bitboards[(cell >> 1) as usize] |= 1 << square.0;
+            if cell >> 1 == Piece::King as u8 {
+                self.king_squares[color] = Some(square);
+            }
+        }
+        self.cells[square.0 as usize] = cell;
+    }
+
+    /// Give the move to the other side, updating the hash to match
+    fn pass_turn(&mut self) {
+        self.turn = self.turn.opposite();
+        self.key ^= ZOBRIST.black_to_move;
+    }
+
+    /// Set the castling right `right`, see `castling_right`, to the rook on `file`, updating the
+    /// hash to match
+    fn set_castling_availability(&mut self, right: usize, file: Option<File>) {
+        let keys = &ZOBRIST.castling[right];
+        if let Some(old) = self.castling_availability[right] {
+            self.key ^= keys[old.index() as usize];
+        }
+        if let Some(new) = file {
+            self.key ^= keys[new.index() as usize];
+        }
+        self.castling_availability[right] = file;
+    }
+
+    /// Board without any pieces or castling rights, White to move. Start from this to set up
+    /// a position with `put_piece`
+    pub fn empty() -> Board {
+        Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1").unwrap()
+    }
+
+    /// Piece and color on `square`, if any
+    pub fn piece_at(&self, square: Mailbox64Index) -> Option<(Piece, Color)> {
+        match self.cells[square.0 as usize] {
+            0 => None,
+            cell => Some(piece_from_u8(cell)),
+        }
+    }
+
+    /// Put a piece on `square`, replacing whatever stood there. Castling rights and the en
+    /// passant target the change invalidates are dropped
+    pub fn put_piece(&mut self, square: Mailbox64Index, piece: Piece, color: Color) -> Result<(), EditError> {
+        if piece == Piece::Pawn && (square.rank() == Rank::First || square.rank() == Rank::Eighth) {
+            return Err(EditError::PawnOnBackRank(square));
+        }
+        if piece == Piece::King && self.king_square(color).is_some_and(|king| king != square) {
+            return Err(EditError::SecondKing(color));
+        }
+        self.set_cell(square, new_piece(piece, color));
+        self.drop_stale_rights();
+        Ok(())
+    }
+
+    /// Take the piece off `square`, returning it. Castling rights and the en passant target
+    /// the change invalidates are dropped
+    pub fn remove_piece(&mut self, square: Mailbox64Index) -> Option<(Piece, Color)> {
+        let removed = self.piece_at(square)?;
+        self.set_cell(square, 0);
+        self.drop_stale_rights();
+        Some(removed)
+    }
+
+    /// Move the piece on `from` to `to` regardless of the rules, replacing whatever stood there
+    pub fn move_piece(&mut self, from: Mailbox64Index, to: Mailbox64Index) -> Result<(), EditError> {
+        let (piece, color) = self.piece_at(from).ok_or(EditError::EmptySquare(from))?;
+        if from == to {
+            return Ok(());
+        }
+        if piece == Piece::Pawn && (to.rank() == Rank::First || to.rank() == Rank::Eighth) {
+            return Err(EditError::PawnOnBackRank(to));
+        }
+        self.set_cell(from, 0);
+        self.set_cell(to, new_piece(piece, color));
+        self.drop_stale_rights();
+        Ok(())
+    }
+
+    /// Hand the move to `color`. The en passant target belonged to the other side's turn, so
+    /// it is dropped when the turn changes
+    pub fn set_turn(&mut self, color: Color) {
+        if color != self.turn {
+            self.en_passant_target_square = None;
+            self.pass_turn();
+        }
+    }
+
+    /// Give `color` the right to castle with the rook on `rook_file`, or take it away with `None`.
+    /// Whether it is the kingside or queenside right follows from which side of the king the
+    /// rook stands on, so this also sets up Chess960 positions
+    pub fn set_castling_right(&mut self, color: Color, kingside: bool, rook_file: Option<File>) -> Result<(), EditError> {
+        if let Some(file) = rook_file {
+            if !self.can_castle_with(color, kingside, file) {
+                return Err(EditError::Castling(color, file));
+            }
+        }
+        self.set_castling_availability(castling_right(color, kingside), rook_file);
+        Ok(())
+    }
+
+    /// Set the square behind a pawn of the side not to move that has just advanced two squares,
+    /// or clear it with `None`
+    pub fn set_en_passant_target_square(&mut self, square: Option<Mailbox64Index>) -> Result<(), EditError> {
+        if let Some(square) = square {
+            if !self.is_en_passant_target(square) {
+                return Err(EditError::EnPassant(square));
+            }
+        }
+        self.en_passant_target_square = square;
+        Ok(())
+    }
+
+    /// Set the number of halfmoves since the last capture or pawn move
+    pub fn set_halfmove_clock(&mut self, halfmove_clock: u8) {
+        self.halfmove_clock = halfmove_clock;
+    }
+
+    /// Set the move number, starting at 1 and incremented after Black's move
+    pub fn set_fullmove_clock(&mut self, fullmove_clock: usize) {
+        self.fullmove_clock = fullmove_clock.max(1);
+    }
+
+    /// Whether the king of `color` stands on its back rank with a rook of its color on `file`,
+    /// on the given side
+    fn can_castle_with(&self, color: Color, kingside: bool, file: File) -> bool {
+        let back_rank = Rank::First.relative(color);
+        let Some(king) = self.king_square(color).filter(|king| king.rank() == back_rank) else {
+            return false;
+        };
+        let rook_on_side = if kingside { file > king.file() } else { file < king.file() };
+        rook_on_side && self.piece_at(Mailbox64Index::from_file_rank(file, back_rank)) == Some((Piece::Rook, color))
+    }
+
+    /// Whether `square` could be the en passant target: an empty square on the sixth rank of the
+    /// side to move, with an enemy pawn one rank further on and the square it came from empty
+    fn is_en_passant_target(&self, square: Mailbox64Index) -> bool {
+        let enemy = self.turn.opposite();
+        let pawn = Mailbox64Index::from_file_rank(square.file(), Rank::Fifth.relative(self.turn));
+        let origin = Mailbox64Index::from_file_rank(square.file(), Rank::Seventh.relative(self.turn));
+        square.rank() == Rank::Sixth.relative(self.turn)
+            && self.piece_at(square).is_none()
+            && self.piece_at(origin).is_none()
+            && self.piece_at(pawn) == Some((Piece::Pawn, enemy))
+    }
+
+    /// Drop the castling rights and en passant target an edit has made impossible
+    fn drop_stale_rights(&mut self) {
+        self.drop_impossible_castling_rights();
+        if let Some(square) = self.en_passant_target_square {
+            if !self.is_en_passant_target(square) {
+                self.en_passant_target_square = None;
+            }
+        }
+    }
+
+    /// Drop the castling rights without the king on its back rank and the rook on its file
+    fn drop_impossible_castling_rights(&mut self) {
+        for color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                let right = castling_right(color, kingside);
+                if let Some(file) = self.castling_availability[right] {
+                    if !self.can_castle_with(color, kingside, file) {
+                        self.set_castling_availability(right, None);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Side to move
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
+    /// Castling rights as the files of the rooks castling with, indexed by `castling_right`
+    pub fn castling_availability(&self) -> [Option<File>; 4] {
+        self.castling_availability
+    }
+
+    /// Whether the king of `color` moving `from` -> `to` asks to castle, and on which side.
+    /// That is the case when it is dropped on its own castling rook, or moves two files
+    /// to the g or c file while the matching right is still held
+    pub fn castling_side(&self, color: Color, from: Mailbox64Index, to: Mailbox64Index) -> Option<bool> {
+        if from.rank() != to.rank() {
+            return None;
+        }
+        for kingside in [true, false] {
+            if let Some(rook_file) = self.castling_availability[castling_right(color, kingside)] {
+                let onto_rook = to.file() == rook_file && self.cells[to.0 as usize] != 0
+                    && piece_from_u8(self.cells[to.0 as usize]).1 == color;
+                let two_files = from.file().index().abs_diff(to.file().index()) == 2
+                    && to.file() == if kingside { File::G } else { File::C };
+                if onto_rook || two_files {
+                    return Some(kingside);
+                }
+            }
+        }
+        None
+    }
+
+    /// Square a pawn can capture onto en passant, if the last move was a double push
+    pub fn en_passant_target_square(&self) -> Option<Mailbox64Index> {
+        self.en_passant_target_square
+    }
+
+    /// Number of halfmoves since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> u8 {
+        self.halfmove_clock
+    }
+
+    /// Number of the current full move, starting at 1 and incremented after each Black move
+    pub fn fullmove_clock(&self) -> usize {
+        self.fullmove_clock
+    }
+
+    /// Zobrist hash of the position, ignoring the move clocks. The en passant square only counts
+    /// when a pawn of the side to move stands next to the pawn that can be taken, as otherwise it
+    /// changes nothing. Whether that pawn may legally take is left to `repetition_hash`
+    pub fn zobrist(&self) -> u64 {
+        match self.en_passant_target_square {
+            Some(target) if self.en_passant_capturers(target).next().is_some() => self.key ^ ZOBRIST.en_passant[target.file().index() as usize],
+            _ => self.key,
+        }
+    }
+
+    /// Pawns of the side to move standing next to the pawn that double pushed past `target`
+    fn en_passant_capturers(&self, target: Mailbox64Index) -> impl Iterator<Item = Mailbox64Index> + '_ {
+        // The pawn that double pushed sits one rank further from the side to move than the target
+        let victim_rank = Rank::Fifth.relative(self.turn);
+        let capturer = new_piece(Piece::Pawn, self.turn);
+        [target.file().offset(-1), target.file().offset(1)]
+            .into_iter()
+            .flatten()
+            .map(move |file| Mailbox64Index::from_file_rank(file, victim_rank))
+            .filter(move |square| self.cells[square.0 as usize] == capturer)
+    }
+
+    /// The en passant target square, only if a legal en passant capture onto it exists
+    pub fn en_passant_capture_square(&self) -> Option<Mailbox64Index> {
+        let target = self.en_passant_target_square?;
+        self.en_passant_capturers(target)
+            .any(|square| generate_legal(self, square).iter().any(|mv| mv.en_passant))
+            .then_some(target)
+    }
+
+    /// Zobrist hash of what makes two positions the same for the repetition rule: like `zobrist`,
+    /// but with the en passant square only counted when the capture is legal
+    pub fn repetition_hash(&self) -> u64 {
+        self.repetition_hash_from(self.zobrist())
+    }
+
+    /// `repetition_hash` from `zobrist`, the `zobrist` of this board, for callers that need both.
+    /// Only a position with a pawn next to one that just double pushed needs more than that
+    pub fn repetition_hash_from(&self, zobrist: u64) -> u64 {
+        match self.en_passant_target_square {
+            Some(target) if self.en_passant_capturers(target).next().is_some() && self.en_passant_capture_square().is_none() => {
+                zobrist ^ ZOBRIST.en_passant[target.file().index() as usize]
+            },
+            _ => zobrist,
+        }
+    }
+
+    /// Whether `other` is the same position as this one for the repetition rule: the same pieces
+    /// on the same squares, side to move, castling rights and en passant capture, whatever the
+    /// move clocks say
+    pub fn is_repetition_of(&self, other: &Board) -> bool {
+        self.cells == other.cells
+            && self.turn == other.turn
+            && self.castling_availability == other.castling_availability
+            && self.en_passant_capture_square() == other.en_passant_capture_square()
+    }
+
+    /// Squares whose contents differ from `other`, in index order
+    pub fn diff(&self, other: &Board) -> Vec<SquareChange> {
+        self.cells.iter().zip(other.cells.iter()).enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(index, (&before, &after))| SquareChange { square: Mailbox64Index(index as u8), before, after })
+            .collect()
+    }
+
+    /// Pieces that moved between this board and `other`, found by pairing each square a piece
+    /// left with one where the same piece turned up. A promoted pawn pairs with the new piece of
+    /// its color instead. Anything left unpaired simply appeared or disappeared
+    pub fn slides(&self, other: &Board) -> Vec<Slide> {
+        let changes = self.diff(other);
+        let mut left: Vec<&SquareChange> = changes.iter().filter(|change| change.before != 0).collect();
+        let mut arrived: Vec<&SquareChange> = changes.iter().filter(|change| change.after != 0).collect();
+        let mut slides = Vec::new();
+        let same_piece = |from: &SquareChange, to: &SquareChange| from.before == to.after;
+        let same_color = |from: &SquareChange, to: &SquareChange| from.before & 1 == to.after & 1;
+        for matches in [same_piece, same_color] {
+            left.retain(|from| {
+                let Some(position) = arrived.iter().position(|to| matches(from, to)) else {
+                    return true;
+                };
+                let to = arrived.remove(position);
+                slides.push(Slide { from: from.square, to: to.square, cell: to.after });
+                false
+            });
+        }
+        slides
+    }
+
+    /// Apply a move, returning the information needed to take it back with `unmake_move`
+    pub fn make_move(&mut self, mv: &Move) -> UndoInfo {
+        let moved = self.cells[mv.from.0 as usize];
+        let (_, color) = piece_from_u8(moved);
+        debug_assert!(color == self.turn, "make_move called for a piece of the side not to move");
+        let captured_square = if mv.en_passant { en_passant_victim_square(mv.from, mv.to) } else { mv.to };
+        let undo = UndoInfo {
+            mv: mv.clone(),
+            moved,
+            // When castling the king may land on its own rook, which is not a capture
+            captured: if mv.castle { 0 } else { self.cells[captured_square.0 as usize] },
+            castling_availability: self.castling_availability,
+            en_passant_target_square: self.en_passant_target_square,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_clock: self.fullmove_clock,
+        };
+
+        if mv.castle {
+            // King and rook may swap or land on each other's squares, so lift both first
+            let (rook_from, rook_to) = castling_rook_squares(&self.castling_availability, mv.from, mv.to);
+            let rook = self.cells[rook_from.0 as usize];
+            self.set_cell(mv.from, 0);
+            self.set_cell(rook_from, 0);
+            self.set_cell(mv.to, moved);
+            self.set_cell(rook_to, rook);
+        } else {
+            self.set_cell(captured_square, 0);
+            self.set_cell(mv.to, match mv.promotion {
+                Some(promotion) => new_piece(promotion, color),
+                None => moved,
+            });
+            self.set_cell(mv.from, 0);
+        }
+
+        self.en_passant_target_square = None;
+        if mv.double_push {
+            self.en_passant_target_square = Some(Mailbox64Index((mv.from.0 + mv.to.0) / 2));
+        }
+
+        self.update_castling_availability(mv, color);
+
+        if mv.piece == Piece::Pawn || mv.captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if color == Color::Black {
+            self.fullmove_clock += 1;
+        }
+        self.pass_turn();
+        undo
+    }
+
+    /// Drop castling rights a move gives up: all of them for the side whose king moves, and
+    /// the right of a rook that leaves its starting square or is captured on it
+    fn update_castling_availability(&mut self, mv: &Move, color: Color) {
+        if mv.piece == Piece::King {
+            self.set_castling_availability(castling_right(color, true), None);
+            self.set_castling_availability(castling_right(color, false), None);
+        }
+        for (right, back_rank) in [(0, Rank::First), (1, Rank::First), (2, Rank::Eighth), (3, Rank::Eighth)] {
+            if let Some(file) = self.castling_availability[right] {
+                let rook_square = Mailbox64Index::from_file_rank(file, back_rank);
+                if mv.from == rook_square || mv.to == rook_square {
+                    self.set_castling_availability(right, None);
+                }
+            }
+        }
+    }
+
+    /// Pass the turn to the other side without moving, returning the en passant target square
+    /// it clears so `unmake_null_move` can restore it. The move clocks are left untouched
+    pub fn make_null_move(&mut self) -> Option<Mailbox64Index> {
+        self.pass_turn();
+        self.en_passant_target_square.take()
+    }
+
+    /// Take back a `make_null_move`
+    pub fn unmake_null_move(&mut self, en_passant_target_square: Option<Mailbox64Index>) {
+        self.pass_turn();
+        self.en_passant_target_square = en_passant_target_square;
+    }
+
+    /// Take back a move previously applied with `make_move`
+    pub fn unmake_move(&mut self, undo: UndoInfo) {
+        let mv = &undo.mv;
+        if mv.castle {
+            let (rook_from, rook_to) = castling_rook_squares(&undo.castling_availability, mv.from, mv.to);
+            let rook = self.cells[rook_to.0 as usize];
+            self.set_cell(mv.to, 0);
+            self.set_cell(rook_to, 0);
+            self.set_cell(rook_from, rook);
+            self.set_cell(mv.from, undo.moved);
+        } else {
+            self.set_cell(mv.from, undo.moved);
+            if mv.en_passant {
+                self.set_cell(mv.to, 0);
+                self.set_cell(en_passant_victim_square(mv.from, mv.to), undo.captured);
+            } else {
+                self.set_cell(mv.to, undo.captured);
+            }
+        }
+
+        for (right, file) in undo.castling_availability.into_iter().enumerate() {
+            self.set_castling_availability(right, file);
+        }
+        self.en_passant_target_square = undo.en_passant_target_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_clock = undo.fullmove_clock;
+        self.pass_turn();
+    }
+}
+
+/// A move of `piece` from `from` to `to`, with everything needed to apply and describe it
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Move {
+    /// Square the piece leaves
+    pub from: Mailbox64Index,
+    /// Square the piece lands on
+    pub to: Mailbox64Index,
+    /// The piece that moves
+    pub piece: Piece,
+    /// Piece taken by the move, including a pawn taken en passant
+    pub captured: Option<Piece>,
+    /// Piece a pawn turns into on the last rank
+    pub promotion: Option<Piece>,
+    /// The king castles: it lands on the g or c file and the rook next to it, on f or d
+    pub castle: bool,
+    /// A pawn captures the pawn that just double pushed past it
+    pub en_passant: bool,
+    /// A pawn advances two squares from its home row
+    pub double_push: bool,
+    /// The move puts the opponent in check. Filled in by the legal generator, `from_squares`
+    /// and `castling` leave it unset
+    pub gives_check: bool,
+}
+
+impl Move {
+    /// The castling move of the king on `king`, landing on the g file for kingside or the c file
+    pub fn castling(king: Mailbox64Index, kingside: bool) -> Move {
+        Move {
+            from: king,
+            to: Mailbox64Index::from_file_rank(if kingside { File::G } else { File::C }, king.rank()),
+            piece: Piece::King,
+            captured: None,
+            promotion: None,
+            castle: true,
+            en_passant: false,
+            double_push: false,
+            gives_check: false,
+        }
+    }
+
+    /// Build the move of the piece on `from` to `to` in `board`, working out the captured
+    /// piece and special-move flags from the position
+    pub fn from_squares(board: &Board, from: Mailbox64Index, to: Mailbox64Index, promotion: Option<Piece>) -> Move {
+        let (piece, color) = piece_from_u8(board.cells[from.0 as usize]);
+        if piece == Piece::King {
+            if let Some(kingside) = board.castling_side(color, from, to) {
+                return Move::castling(from, kingside);
+            }
+        }
+        let target = board.cells[to.0 as usize];
+        let en_passant = piece == Piece::Pawn
+            && from.file() != to.file()
+            && target == 0
+            && board.en_passant_target_square == Some(to);
+        let captured = if en_passant {
+            Some(Piece::Pawn)
+        } else if target != 0 {
+            Some(piece_from_u8(target).0)
+        } else {
+            None
+        };
+        Move {
+            castle: false,
+            double_push: piece == Piece::Pawn && from.0.abs_diff(to.0) == 16,
+            en_passant,
+            gives_check: false,
+            captured,
+            promotion,
+            piece,
+            from,
+            to,
+        }
+    }
+
+    /// The move in UCI long algebraic notation, like "e2e4" or "e7e8q". Castling is written as
+    /// the king's two-file step, "e1g1"
+    pub fn to_uci(&self) -> String {
+        let mut uci = format!("{}{}", self.from, self.to);
+        if let Some(promotion) = self.promotion {
+            uci.push(promotion.to_char().to_ascii_lowercase());
+        }
+        uci
+    }
+
+    /// Read a move in UCI long algebraic notation for `board`. Only the syntax is checked, the
+    /// move still has to be matched against the legal moves, e.g. with `legal_move`
+    pub fn from_uci(board: &Board, text: &str) -> Option<Move> {
+        let from = Mailbox64Index::try_from(text.get(0..2)?).ok()?;
+        let to = Mailbox64Index::try_from(text.get(2..4)?).ok()?;
+        let mut rest = text[4..].chars();
+        let promotion = match (rest.next(), rest.next()) {
+            (None, _) => None,
+            (Some(c @ ('q' | 'r' | 'b' | 'n')), None) => Piece::from_char(c),
+            _ => return None,
+        };
+        if board.cells[from.0 as usize] == 0 {
+            return None;
+        }
+        Some(Move::from_squares(board, from, to, promotion))
+    }
+}
+
+/// A square whose cell differs between two boards, see `Board::diff`
+pub struct SquareChange {
+    /// The square that changed
+    pub square: Mailbox64Index,
+    /// Its cell on the first board
+    pub before: u8,
+    /// Its cell on the second board
+    pub after: u8,
+}
+
+/// A piece that went from one square to another, see `Board::slides`
+pub struct Slide {
+    /// Square the piece left
+    pub from: Mailbox64Index,
+    /// Square the piece arrived on
+    pub to: Mailbox64Index,
+    /// The piece as it stands on `to`, which differs from the one that left `from` on promotion
+    pub cell: u8,
+}
+
+/// Everything `make_move` overwrites that cannot be recomputed from the move itself
+pub struct UndoInfo {
+    mv: Move,
+    moved: u8,
+    captured: u8,
+    castling_availability: [Option<File>; 4],
+    en_passant_target_square: Option<Mailbox64Index>,
+    halfmove_clock: u8,
+    fullmove_clock: usize,
+}
+
+/// The light squares, one bit per `Mailbox64Index`
+pub const LIGHT_SQUARES: u64 = 0xaa55_aa55_aa55_aa55;
+
+/// Squares set in `bitboard`, one bit per `Mailbox64Index`, in index order
+pub fn squares(mut bitboard: u64) -> impl Iterator<Item = Mailbox64Index> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let index = bitboard.trailing_zeros() as u8;
+        bitboard &= bitboard - 1;
+        Some(Mailbox64Index(index))
+    })
+}
+
+/// Square of the pawn captured en passant by a pawn moving `from` -> `to`
+pub fn en_passant_victim_square(from: Mailbox64Index, to: Mailbox64Index) -> Mailbox64Index {
+    Mailbox64Index::from_file_rank(to.file(), from.rank())
+}
+
+/// Index into `castling_availability` of the right for the given color and side
+pub(crate) fn castling_right(color: Color, kingside: bool) -> usize {
+    match (color, kingside) {
+        (Color::White, true) => 0,
+        (Color::White, false) => 1,
+        (Color::Black, true) => 2,
+        (Color::Black, false) => 3,
+    }
+}
+
+/// Rook origin and destination when the king castles from `king_from` to `king_to`.
+/// The rook starts on whichever file the castling right records, so this also covers Chess960
+pub(crate) fn castling_rook_squares(castling_availability: &[Option<File>; 4], king_from: Mailbox64Index, king_to: Mailbox64Index) -> (Mailbox64Index, Mailbox64Index) {
+    let back_rank = king_from.rank();
+    let color = if back_rank == Rank::First { Color::White } else { Color::Black };
+    let kingside = king_to.file() == File::G;
+    let rook_file = castling_availability[castling_right(color, kingside)]
+        .expect("castling without the matching castling right");
+    let rook_to_file = if kingside { File::F } else { File::D };
+    (Mailbox64Index::from_file_rank(rook_file, back_rank), Mailbox64Index::from_file_rank(rook_to_file, back_rank))
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Mailbox120Index(pub u8);
+/// Square as an index into `Board::cells`, 0 being a8 and 63 h1
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Mailbox64Index(pub u8);
+
+impl Mailbox64Index {
+    /// The square alone, as a bitboard
+    pub fn bit(self) -> u64 {
+        1 << self.0
+    }
+
+    /// Square on `file` and `rank`
+    pub fn from_file_rank(file: File, rank: Rank) -> Mailbox64Index {
+        // Cells are stored from a8 onwards, so rank 8 is row 0
+        Mailbox64Index(file.index() + (7 - rank.index()) * 8)
+    }
+
+    /// File of the square
+    pub fn file(self) -> File {
+        File::ALL[(self.0 % 8) as usize]
+    }
+
+    /// Rank of the square
+    pub fn rank(self) -> Rank {
+        Rank::ALL[(7 - self.0 / 8) as usize]
+    }
+}
+
+/// Square in algebraic notation, like "e4"
+impl TryFrom<&str> for Mailbox64Index {
+    type Error = ();
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut chars = value.chars();
+        match (chars.next().and_then(File::from_char), chars.next().and_then(Rank::from_char), chars.next()) {
+            (Some(file), Some(rank), None) => Ok(Mailbox64Index::from_file_rank(file, rank)),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Mailbox64Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.file(), self.rank())
+    }
+}
+
+/// Column of the board, from a on White's left to h
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum File {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+}
+
+impl File {
+    /// Every file, from a to h
+    pub const ALL: [File; 8] = [File::A, File::B, File::C, File::D, File::E, File::F, File::G, File::H];
+
+    /// File number `index`, 0 being a
+    pub fn from_index(index: u8) -> Option<File> {
+        File::ALL.get(index as usize).copied()
+    }
+
+    /// Number of the file, 0 for a
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// File `by` files towards h, or towards a when negative, if that is still on the board
+    pub fn offset(self, by: i8) -> Option<File> {
+        File::from_index(u8::try_from(self as i8 + by).ok()?)
+    }
+
+    /// File written as its letter, like 'e'
+    pub fn from_char(c: char) -> Option<File> {
+        File::ALL.into_iter().find(|file| file.to_char() == c)
+    }
+
+    /// Letter of the file, like 'e'
+    pub fn to_char(self) -> char {
+        (b'a' + self as u8) as char
+    }
+
+    /// Squares of the file, one bit per `Mailbox64Index`
+    pub fn bitboard(self) -> u64 {
+        0x0101_0101_0101_0101 << self.index()
+    }
+}
+
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// Row of the board, from White's first rank to the eighth
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Rank {
+    First,
+    Second,
+    Third,
+    Fourth,
+    Fifth,
+    Sixth,
+    Seventh,
+    Eighth,
+}
+
+impl Rank {
+    /// Every rank, from the first to the eighth
+    pub const ALL: [Rank; 8] = [Rank::First, Rank::Second, Rank::Third, Rank::Fourth, Rank::Fifth, Rank::Sixth, Rank::Seventh, Rank::Eighth];
+
+    /// Rank number `index`, 0 being the first rank
+    pub fn from_index(index: u8) -> Option<Rank> {
+        Rank::ALL.get(index as usize).copied()
+    }
+
+    /// Number of the rank, 0 for the first rank
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// Rank `by` ranks towards the eighth, or towards the first when negative, if that is
+    /// still on the board
+    pub fn offset(self, by: i8) -> Option<Rank> {
+        Rank::from_index(u8::try_from(self as i8 + by).ok()?)
+    }
+
+    /// This rank as seen from the side of `color`, so `Rank::First.relative(&Color::Black)`
+    /// is Black's back rank, the eighth
+    pub fn relative(self, color: Color) -> Rank {
+        match color {
+            Color::White => self,
+            Color::Black => Rank::ALL[7 - self as usize],
+        }
+    }
+
+    /// Rank written as its digit, like '4'
+    pub fn from_char(c: char) -> Option<Rank> {
+        Rank::ALL.into_iter().find(|rank| rank.to_char() == c)
+    }
+
+    /// Digit of the rank, like '4'
+    pub fn to_char(self) -> char {
+        (b'1' + self as u8) as char
+    }
+
+    /// Squares of the rank, one bit per `Mailbox64Index`
+    pub fn bitboard(self) -> u64 {
+        // Cells are stored from the eighth rank down
+        0xff << ((7 - self.index()) * 8)
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl From<Mailbox64Index> for Mailbox120Index {
+    fn from(value: Mailbox64Index) -> Self {
+        Mailbox120Index(MAILBOX64[value.0 as usize])
+    }
+}
+
+/// The square of the board at a `Mailbox120Index`, which fails for the border around it
+impl TryFrom<Mailbox120Index> for Mailbox64Index {
+    type Error = ();
+
+    fn try_from(value: Mailbox120Index) -> Result<Self, Self::Error> {
+        match MAILBOX120.get(value.0 as usize) {
+            Some(&index) if index >= 0 => Ok(Mailbox64Index(index as u8)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The square `offset` steps of the 10 wide mailbox away from `index`, if still on the board
+fn offset_index(index: Mailbox64Index, offset: i8) -> Option<Mailbox64Index> {
+    let target = Mailbox120Index::from(index).0 as i8 + offset;
+    Mailbox64Index::try_from(Mailbox120Index(u8::try_from(target).ok()?)).ok()
+}
+
+pub(crate) fn offset_index_2d(index: Mailbox64Index, file_offset: i8, rank_offset: i8) -> Option<Mailbox64Index> {
+    if !(-2..=2).contains(&file_offset) || !(-2..=2).contains(&rank_offset) {
+        return None;
+    }
+    offset_index(index, file_offset + rank_offset * 10)
+}
+
+const MAILBOX120: [i8; 120] = [
+    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    -1,  0,  1,  2,  3,  4,  5,  6,  7, -1,
+    -1,  8,  9, 10, 11, 12, 13, 14, 15, -1,
+    -1, 16, 17, 18, 19, 20, 21, 22, 23, -1,
+    -1, 24, 25, 26, 27, 28, 29, 30, 31, -1,
+    -1, 32, 33, 34, 35, 36, 37, 38, 39, -1,
+    -1, 40, 41, 42, 43, 44, 45, 46, 47, -1,
+    -1, 48, 49, 50, 51, 52, 53, 54, 55, -1,
+    -1, 56, 57, 58, 59, 60, 61, 62, 63, -1,
+    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1
+];
+
+const MAILBOX64: [u8; 64] = [
+    21, 22, 23, 24, 25, 26, 27, 28,
+    31, 32, 33, 34, 35, 36, 37, 38,
+    41, 42, 43, 44, 45, 46, 47, 48,
+    51, 52, 53, 54, 55, 56, 57, 58,
+    61, 62, 63, 64, 65, 66, 67, 68,
+    71, 72, 73, 74, 75, 76, 77, 78,
+    81, 82, 83, 84, 85, 86, 87, 88,
+    91, 92, 93, 94, 95, 96, 97, 98
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::chess_engine::generate_all_legal_moves;
+
+    fn square(name: &str) -> Mailbox64Index {
+        Mailbox64Index::try_from(name).unwrap()
+    }
+
+    #[test]
+    fn board_editing() {
+        let mut board = Board::empty();
+        board.put_piece(square("e1"), Piece::King, Color::White).unwrap();
+        board.put_piece(square("h1"), Piece::Rook, Color::White).unwrap();
+        board.put_piece(square("e8"), Piece::King, Color::Black).unwrap();
+        board.put_piece(square("d5"), Piece::Pawn, Color::Black).unwrap();
+        board.put_piece(square("e5"), Piece::Pawn, Color::White).unwrap();
+        assert_eq!(board.put_piece(square("a8"), Piece::Pawn, Color::White), Err(EditError::PawnOnBackRank(square("a8"))));
+        assert_eq!(board.put_piece(square("a1"), Piece::King, Color::White), Err(EditError::SecondKing(Color::White)));
+
+        board.set_castling_right(Color::White, true, Some(File::H)).unwrap();
+        assert_eq!(board.set_castling_right(Color::White, false, Some(File::A)), Err(EditError::Castling(Color::White, File::A)));
+        board.set_en_passant_target_square(Some(square("d6"))).unwrap();
+        assert_eq!(board.set_en_passant_target_square(Some(square("f6"))), Err(EditError::EnPassant(square("f6"))));
+        assert_eq!(board.to_fen(), "4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 1");
+
+        // Moving the rook or the pawn takes away the right that depended on it
+        board.move_piece(square("h1"), square("h2")).unwrap();
+        assert_eq!(board.remove_piece(square("d5")), Some((Piece::Pawn, Color::Black)));
+        assert_eq!(board.to_fen(), "4k3/8/8/4P3/8/8/7R/4K3 w - - 0 1");
+        assert_eq!(board.king_square(Color::White), Some(square("e1")));
+        assert_eq!(board.pieces(Color::White).count(), 3);
+    }
+
+    #[test]
+    fn bitboards_follow_the_cells() {
+        let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let start = board.clone();
+        // Castling, a double push answered en passant, promotions, and one capturing
+        let mut undos = Vec::new();
+        for uci in ["e1g1", "c7c5", "d5c6", "h3g2", "c6d7", "e8f8", "d7d8q", "a8d8", "a2a3", "g2f1q"] {
+            undos.push(board.make_move(&Move::from_uci(&board, uci).unwrap()));
+            for piece in [Piece::Pawn, Piece::Bishop, Piece::Rook, Piece::Knight, Piece::Queen, Piece::King] {
+                for color in [Color::White, Color::Black] {
+                    let expected = (0..64).map(Mailbox64Index).filter(|&square| board.piece_at(square) == Some((piece, color))).fold(0, |bitboard, square| bitboard | square.bit());
+                    assert_eq!(board.bitboard(piece, color), expected, "{:?} {:?} after {}", piece, color, uci);
+                }
+            }
+        }
+        assert_eq!(squares(board.occupancy(Color::White)).count(), board.pieces(Color::White).count());
+        while let Some(undo) = undos.pop() {
+            board.unmake_move(undo);
+        }
+        assert_eq!(board.bitboards, start.bitboards);
+        assert_eq!(board.occupied(), start.occupied());
+        assert_eq!(File::C.bitboard() & Rank::Fifth.bitboard(), square("c5").bit());
+        assert_eq!(LIGHT_SQUARES & square("a8").bit(), square("a8").bit());
+        assert_eq!(LIGHT_SQUARES & square("a1").bit(), 0);
+        assert_eq!(LIGHT_SQUARES.count_ones(), 32);
+    }
+
+    #[test]
+    fn mailbox_conversions() {
+        for index in (0..64).map(Mailbox64Index) {
+            assert_eq!(Mailbox64Index::try_from(Mailbox120Index::from(index)), Ok(index));
+        }
+        assert_eq!(Mailbox64Index::try_from(Mailbox120Index(20)), Err(()));
+        assert_eq!(Mailbox64Index::try_from(Mailbox120Index(120)), Err(()));
+        assert_eq!(offset_index_2d(square("e4"), 1, -2), Some(square("f6")));
+        assert_eq!(offset_index_2d(square("h1"), 1, 0), None);
+        assert_eq!(offset_index_2d(square("a8"), 0, -2), None);
+    }
+
+    #[test]
+    fn fen_drops_impossible_castling_rights() {
+        let board = Board::from_fen("r3k3/8/8/8/8/8/8/4K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1");
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K1R1 w HAh - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/R3K1R1 w Q - 0 1");
+        let board = Board::from_fen("8/8/8/8/8/8/4K3/R6R w KQ - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "8/8/8/8/8/8/4K3/R6R w - - 0 1");
+    }
+
+    #[test]
+    fn fen_rejects_pawns_on_the_back_ranks() {
+        assert_eq!(Board::from_fen("P3k3/8/8/8/8/8/8/4K3 w - - 0 1").err(), Some(FenError::PawnOnBackRank(square("a8"))));
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K2p w - - 0 1").err(), Some(FenError::PawnOnBackRank(square("h1"))));
+    }
+
+    #[test]
+    fn hash_follows_moves_and_edits() {
+        // The hash kept up to date along the way matches the one of the same position set up
+        // from scratch
+        let fresh = |board: &Board| Board::from_fen(&board.to_fen()).unwrap().zobrist();
+        for fen in ["r3k2r/1P6/8/3pP3/8/8/6p1/R3K2R w KQkq d6 0 1", "rn2k1r1/ppp1pp1p/3p2p1/5bn1/P7/2N2B2/1PPPPP2/2BNK1RR w Gkq - 4 11"] {
+            let mut board = Board::from_fen(fen).unwrap();
+            let before = board.zobrist();
+            for mv in generate_all_legal_moves(&board) {
+                let undo = board.make_move(&mv);
+                assert_eq!(board.zobrist(), fresh(&board), "after {}", mv.to_uci());
+                board.unmake_move(undo);
+                assert_eq!(board.zobrist(), before);
+            }
+            let en_passant = board.make_null_move();
+            assert_eq!(board.zobrist(), fresh(&board));
+            board.unmake_null_move(en_passant);
+            assert_eq!(board.zobrist(), before);
+        }
+        let mut board = Board::default();
+        board.put_piece(square("e4"), Piece::Knight, Color::Black).unwrap();
+        board.remove_piece(square("h1"));
+        board.set_castling_right(Color::Black, true, None).unwrap();
+        board.set_turn(Color::Black);
+        assert_eq!(board.zobrist(), fresh(&board));
+    }
+
+    #[test]
+    fn repetition_ignores_impossible_en_passant() {
+        // After d5 the e5 pawn is pinned to its king and cannot take en passant
+        let pinned = Board::from_fen("8/8/8/K2pP2r/8/8/8/7k w - d6 0 2").unwrap();
+        let after_shuffle = Board::from_fen("8/8/8/K2pP2r/8/8/8/7k w - - 4 4").unwrap();
+        assert_eq!(pinned.en_passant_capture_square(), None);
+        assert!(pinned.is_repetition_of(&after_shuffle));
+        assert_eq!(pinned.repetition_hash(), after_shuffle.repetition_hash());
+        assert_ne!(pinned.zobrist(), after_shuffle.zobrist());
+
+        let free = Board::from_fen("8/8/8/3pP3/8/8/8/K6k w - d6 0 2").unwrap();
+        assert_eq!(free.en_passant_capture_square(), Some(square("d6")));
+        assert!(!free.is_repetition_of(&Board::from_fen("8/8/8/3pP3/8/8/8/K6k w - - 0 2").unwrap()));
+    }
+
+    #[test]
+    fn chess960_start_positions() {
+        assert_eq!(chess960_fen(518).unwrap(), Board::default().to_fen());
+        assert_eq!(chess960_fen(0).unwrap(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(chess960_fen(959).unwrap(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1");
+        assert!(chess960_fen(960).is_none());
+        let positions: HashSet<String> = (0..960).map(|number| Board::from_fen(&chess960_fen(number).unwrap()).unwrap().to_fen()).collect();
+        assert_eq!(positions.len(), 960);
+    }
+
+    #[test]
+    fn from_moves() {
+        let board = Board::from_moves("startpos", &["e2e4", "c7c5", "g1f3"]).unwrap();
+        assert_eq!(board.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+        let board = Board::from_moves("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", &["a7a8q"]).unwrap();
+        assert_eq!(board.to_fen(), "Q3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(Board::from_moves("startpos", &["e2e4", "e2e4"]).err(), Some(MovesError::IllegalMove { index: 1, uci: "e2e4".to_string() }));
+        assert!(matches!(Board::from_moves("8/8 w - - 0 1", &[]), Err(MovesError::Fen(_))));
+    }
+}
@@ -0,0 +1,1263 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::chess_parts::*;
+use crate::chess_engine::*;
+use crate::chess_eval::evaluate_with;
+use crate::chess_params::Params;
+use crate::chess_syzygy::*;
+use crate::chess_tt::*;
+
+/// Score of being mated right now. Mates further away score closer to zero by one per ply, so
+/// the search prefers the quickest mate and the slowest defeat
+pub const MATE: i32 = 100_000;
+
+/// Nodes searched between looks at the clock and the stop flag
+const NODES_BETWEEN_CHECKS: u64 = 1024;
+
+/// Time kept back from every allocation for the move to reach the board
+const TIME_SAFETY_MARGIN: Duration = Duration::from_millis(50);
+
+/// Moves a game is assumed to still last when the time control does not say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Scores at least this far from zero are mates, found within `MATE - MATE_BOUND` plies
+pub const MATE_BOUND: i32 = MATE - 1000;
+
+/// Score of a tablebase win at the root, less one per ply further away
+const TB_WIN: i32 = MATE_BOUND - MAX_PLY;
+
+/// Scores at least this far from zero are tablebase wins, or mates
+pub const TB_WIN_BOUND: i32 = TB_WIN - MAX_PLY;
+
+/// Centipawns a tablebase win at the root is reported as, less one per ply further away. Well
+/// beyond any evaluation, but not a mate, which the tablebases do not promise
+const TB_WIN_CENTIPAWNS: i32 = 20_000;
+
+/// Deepest iteration of a search without a depth limit
+const MAX_DEPTH: u32 = 64;
+
+/// Highest skill level, at which the engine plays as well as it can
+pub const MAX_SKILL_LEVEL: u8 = 20;
+
+/// Per skill level below the maximum, how far the evaluation may be off in centipawns, and how
+/// much worse than the best a line may score and still get picked
+const SKILL_NOISE: i32 = 15;
+const SKILL_SLACK: i32 = 20;
+
+/// Root lines an engine below full strength picks from
+const SKILL_CANDIDATES: usize = 4;
+
+/// Depth `bench` searches to when not told otherwise
+pub const BENCH_DEPTH: u32 = 7;
+
+/// Positions `bench` searches: the opening, middlegames, tactics and endgames
+const BENCH_POSITIONS: [&str; 10] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r2q1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 9",
+    "2r3k1/1q1nbppp/r3p3/3pP3/pPpP4/P1Q2N2/2RN1PPP/2R4K b - - 0 22",
+    "r1b2rk1/2q1b1pp/p2ppn2/1p6/3QP3/1BN1B3/PPP3PP/R4RK1 w - - 0 14",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "8/5pk1/6p1/8/8/2R3P1/5PK1/r7 b - - 0 40",
+];
+
+/// Memory for the transposition table searches share over a game
+pub const TT_MEGABYTES: usize = 16;
+
+/// Deepest ply the quiescence search goes to, which bounds long series of checks and evasions
+const MAX_PLY: i32 = 128;
+
+/// Depths and margins of the pruning, reductions and extensions of the search, which a tuner
+/// can adjust
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchParams {
+    /// Depth from which a null move may prune a node
+    pub null_move_min_depth: i32,
+    /// Depth from which a null move cutoff gets verified with an ordinary search
+    pub null_move_verify_depth: i32,
+    /// Plies the search after a null move is reduced by, besides the move itself
+    pub null_move_reduction: i32,
+    /// Plies of depth per extra ply of null move reduction
+    pub null_move_reduction_depth: i32,
+    /// Depth from which a node without a move from the transposition table is searched a ply
+    /// shallower, as a node worth searching deeply is bound to have one by the next iteration
+    pub iir_min_depth: i32,
+    /// Depth from which the move from the transposition table is extended a ply when no other
+    /// move comes close to its score
+    pub singular_min_depth: i32,
+    /// How close per ply of depth the other moves have to come to the move from the
+    /// transposition table for it not to be extended
+    pub singular_margin: i32,
+    /// How much a quiet move is assumed to gain at most with one or two plies left, by depth.
+    /// Quiet moves that cannot lift the static evaluation to alpha even so are not searched
+    pub futility_margins: [i32; 3],
+    /// Depth up to which a node whose static evaluation beats beta by a margin per ply of depth
+    /// is taken to fail high without searching it
+    pub reverse_futility_max_depth: i32,
+    /// Margin per ply of depth of the reverse futility pruning
+    pub reverse_futility_margin: i32,
+}
+
+impl SearchParams {
+    /// The depths and margins `search` uses
+    pub const DEFAULT: SearchParams = SearchParams {
+        null_move_min_depth: 3,
+        null_move_verify_depth: 7,
+        null_move_reduction: 2,
+        null_move_reduction_depth: 6,
+        iir_min_depth: 4,
+        singular_min_depth: 8,
+        singular_margin: 2,
+        futility_margins: [0, 200, 500],
+        reverse_futility_max_depth: 6,
+        reverse_futility_margin: 120,
+    };
+
+    /// Every parameter with its name, like "singular_margin" or "futility_margins[2]"
+    pub fn weights_mut(&mut self) -> Vec<(String, &mut i32)> {
+        let SearchParams {
+            null_move_min_depth,
+            null_move_verify_depth,
+            null_move_reduction,
+            null_move_reduction_depth,
+            iir_min_depth,
+            singular_min_depth,
+            singular_margin,
+            futility_margins,
+            reverse_futility_max_depth,
+            reverse_futility_margin,
+        } = self;
+        let mut weights: Vec<(String, &mut i32)> = [
+            ("null_move_min_depth", null_move_min_depth),
+            ("null_move_verify_depth", null_move_verify_depth),
+            ("null_move_reduction", null_move_reduction),
+            ("null_move_reduction_depth", null_move_reduction_depth),
+            ("iir_min_depth", iir_min_depth),
+            ("singular_min_depth", singular_min_depth),
+            ("singular_margin", singular_margin),
+            ("reverse_futility_max_depth", reverse_futility_max_depth),
+            ("reverse_futility_margin", reverse_futility_margin),
+        ].map(|(name, weight)| (name.to_string(), weight)).into();
+        // Nothing is pruned with no plies left, where the quiescence search takes over
+        for (depth, margin) in futility_margins.iter_mut().enumerate().skip(1) {
+            weights.push((format!("futility_margins[{}]", depth), margin));
+        }
+        weights
+    }
+}
+
+impl Default for SearchParams {
+    fn default() -> SearchParams {
+        SearchParams::DEFAULT
+    }
+}
+
+/// Bound of history scores, which approach it the more often a move causes cutoffs
+const HISTORY_MAX: i32 = 16384;
+
+/// What a search looks for and when it ends on its own. Whichever limit is reached first ends
+/// it, though the first iteration always completes
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    /// Deepest iteration to search
+    pub depth: Option<u32>,
+    /// Positions to visit at most
+    pub nodes: Option<u64>,
+    /// Time to search for
+    pub move_time: Option<Duration>,
+    /// Keep searching until stopped, even past the other limits or with nothing left to search
+    pub infinite: bool,
+    /// Best lines to find, each starting with a different move. One when zero
+    pub multi_pv: usize,
+    /// Strength to play at, from 0 to `MAX_SKILL_LEVEL` like the UCI `Skill Level` option. Below
+    /// the maximum the search goes less deep, misjudges positions by up to a few pawns and may
+    /// pick a move that is not the best. Full strength when `None`
+    pub skill_level: Option<u8>,
+    /// Centipawns the side to move at the root thinks a draw is worse for it than an equal
+    /// position. Positive values make it avoid draws, negative values seek them
+    pub contempt: i32,
+    /// Endgame tablebases to play perfectly from and cut the search off with once few pieces
+    /// are left
+    pub tablebases: Option<Arc<Tablebases>>,
+    /// Weights of the evaluation and depths and margins of the search
+    pub params: Arc<Params>,
+}
+
+impl SearchLimits {
+    /// Search `depth` plies deep
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits { depth: Some(depth), ..SearchLimits::default() }
+    }
+
+    /// Search until `nodes` positions are visited
+    pub fn nodes(nodes: u64) -> SearchLimits {
+        SearchLimits { nodes: Some(nodes), ..SearchLimits::default() }
+    }
+
+    /// Search for `time`
+    pub fn move_time(time: Duration) -> SearchLimits {
+        SearchLimits { move_time: Some(time), ..SearchLimits::default() }
+    }
+
+    /// Search until stopped
+    pub fn infinite() -> SearchLimits {
+        SearchLimits { infinite: true, ..SearchLimits::default() }
+    }
+
+    /// The same limits, finding the `multi_pv` best lines
+    pub fn with_multi_pv(self, multi_pv: usize) -> SearchLimits {
+        SearchLimits { multi_pv, ..self }
+    }
+
+    /// The same limits, playing at `skill_level`
+    pub fn with_skill_level(self, skill_level: u8) -> SearchLimits {
+        SearchLimits { skill_level: Some(skill_level), ..self }
+    }
+
+    /// The same limits, probing `tablebases`
+    pub fn with_tablebases(self, tablebases: Arc<Tablebases>) -> SearchLimits {
+        SearchLimits { tablebases: Some(tablebases), ..self }
+    }
+
+    /// The same limits, evaluating and pruning with `params`
+    pub fn with_params(self, params: Arc<Params>) -> SearchLimits {
+        SearchLimits { params, ..self }
+    }
+}
+
+/// Stopping a search early and following it, shared with whoever may want to
+#[derive(Clone, Default)]
+pub struct SearchControl {
+    /// Set from any thread to stop the search as soon as it next looks
+    pub stop: Arc<AtomicBool>,
+    /// Set while pondering: the deadline once the expected move was played, which replaces the
+    /// move time of the limits
+    ponder: Option<Arc<OnceLock<Instant>>>,
+    /// Where to send what the search found as each iteration completes, if anywhere
+    info: Option<Sender<SearchInfo>>,
+}
+
+impl SearchControl {
+    /// Control for pondering, searching the position after the move the opponent is expected to
+    /// play while they think. It does not end before told to stop, as when they play another
+    /// move, or before `ponderhit`, and then not before the time given to it is up
+    pub fn pondering() -> SearchControl {
+        SearchControl { ponder: Some(Arc::default()), ..SearchControl::default() }
+    }
+
+    /// The same control, also sending progress to `info`
+    pub fn with_info(self, info: Sender<SearchInfo>) -> SearchControl {
+        SearchControl { info: Some(info), ..self }
+    }
+
+    /// Whether the search ponders and the expected move has not been played yet
+    pub fn is_pondering(&self) -> bool {
+        self.ponder.as_ref().is_some_and(|hit| hit.get().is_none())
+    }
+
+    /// The opponent played the expected move: from now on the search has `time` to finish.
+    /// Only the first call counts
+    pub fn ponderhit(&self, time: Duration) {
+        if let Some(hit) = &self.ponder {
+            let _ = hit.set(Instant::now() + time);
+        }
+    }
+
+    /// Ask the search to stop and return what it has
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether to stop now, given the deadline of the limits
+    fn should_stop(&self, deadline: Option<&Instant>) -> bool {
+        let deadline = match &self.ponder {
+            Some(hit) => hit.get(),
+            None => deadline,
+        };
+        self.stop.load(Ordering::Relaxed) || deadline.is_some_and(|deadline| Instant::now() >= *deadline)
+    }
+}
+
+/// Time to think about one move with `remaining` on the clock, `increment` added after the move
+/// and `moves_to_go` before the next time control, if one is coming: an even share of the time
+/// left plus most of the increment, always leaving a safety margin on the clock
+pub fn allocate_time(remaining: Duration, increment: Duration, moves_to_go: Option<u32>) -> Duration {
+    let usable = remaining.saturating_sub(TIME_SAFETY_MARGIN);
+    let share = usable / moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1) + increment * 3 / 4;
+    share.min(usable)
+}
+
+/// A search score as a player reads it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Score {
+    /// Advantage in centipawns
+    Centipawns(i32),
+    /// Mate in this many moves, negative when the side to move is the one getting mated
+    Mate(i32),
+}
+
+impl Score {
+    /// Read a search score, from the point of view of the side to move. Tablebase wins and
+    /// losses come out as `TB_WIN_CENTIPAWNS` or a little less
+    pub fn new(score: i32) -> Score {
+        if score >= MATE_BOUND {
+            Score::Mate((MATE - score + 1) / 2)
+        } else if score <= -MATE_BOUND {
+            Score::Mate(-(MATE + score) / 2)
+        } else if score >= TB_WIN_BOUND {
+            Score::Centipawns(TB_WIN_CENTIPAWNS - (TB_WIN - score))
+        } else if score <= -TB_WIN_BOUND {
+            Score::Centipawns(-TB_WIN_CENTIPAWNS + (TB_WIN + score))
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+
+    /// The score as UCI `info score` gives it, like "cp 35" or "mate -2"
+    pub fn to_uci(&self) -> String {
+        match self {
+            Score::Centipawns(centipawns) => format!("cp {}", centipawns),
+            Score::Mate(moves) => format!("mate {}", moves),
+        }
+    }
+}
+
+/// Pawns with two decimals and a sign, like "+0.35", or "#3" and "#-2" for mates
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Centipawns(centipawns) => write!(f, "{:+.2}", *centipawns as f32 / 100.0),
+            Score::Mate(moves) => write!(f, "#{}", moves),
+        }
+    }
+}
+
+/// A line of play the search expects from the root
+#[derive(Clone)]
+pub struct PvLine {
+    /// Moves from the root, starting with the move the line is about
+    pub moves: Vec<Move>,
+    /// Score of the line, like `SearchResult::score`
+    pub score: i32,
+}
+
+/// Progress of a search, sent after every completed iteration
+#[derive(Clone)]
+pub struct SearchInfo {
+    /// Depth of the iteration
+    pub depth: u32,
+    /// Most plies from the root any line reached, counting the quiescence search
+    pub seldepth: u32,
+    /// Score of the best line, like `SearchResult::score`
+    pub score: i32,
+    /// Positions visited so far
+    pub nodes: u64,
+    /// Time since the search started
+    pub time: Duration,
+    /// Positions visited per second
+    pub nps: u64,
+    /// Permille of the transposition table in use
+    pub hashfull: u32,
+    /// The best lines, best first, as many as the search was asked for
+    pub lines: Vec<PvLine>,
+}
+
+impl SearchInfo {
+    /// UCI `info` lines reporting the progress, one for each line
+    pub fn to_uci(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                let pv: Vec<String> = line.moves.iter().map(Move::to_uci).collect();
+                format!(
+                    "info depth {} seldepth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+                    self.depth,
+                    self.seldepth,
+                    index + 1,
+                    Score::new(line.score).to_uci(),
+                    self.nodes,
+                    self.nps,
+                    self.hashfull,
+                    self.time.as_millis(),
+                    pv.join(" "),
+                )
+            })
+            .collect()
+    }
+}
+
+/// What a search found
+pub struct SearchResult {
+    /// Best move in the searched position, or `None` when it has no legal move
+    pub best_move: Option<Move>,
+    /// The best lines, each starting with a different move, best first. As many as asked for
+    /// and the position has legal moves
+    pub lines: Vec<PvLine>,
+    /// Score of the best move in centipawns, from the point of view of the side to move, or
+    /// `MATE` less the plies to a mate, see `Score`
+    pub score: i32,
+    /// Depth of the last completed iteration
+    pub depth: u32,
+    /// Positions visited
+    pub nodes: u64,
+}
+
+impl SearchResult {
+    /// Reply the search expects to the best move, the move to ponder on
+    pub fn ponder_move(&self) -> Option<&Move> {
+        self.lines.first().and_then(|line| line.moves.get(1))
+    }
+}
+
+/// What `bench` measured
+pub struct BenchResult {
+    /// Positions visited over all searches, which only changes with what the search does
+    pub nodes: u64,
+    /// Time all searches took
+    pub time: Duration,
+}
+
+impl BenchResult {
+    /// Positions visited per second
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.time.as_secs_f64().max(0.001)) as u64
+    }
+}
+
+/// Search a fixed set of positions `depth` plies deep each, to compare the speed and the
+/// behavior of the search between builds: the same search visits the same number of nodes
+pub fn bench(depth: u32) -> BenchResult {
+    let start = Instant::now();
+    let mut tt = TranspositionTable::new(TT_MEGABYTES);
+    let nodes = BENCH_POSITIONS
+        .iter()
+        .map(|fen| {
+            // Each position as the start of a new game, so the order they come in does not matter
+            tt.clear();
+            let board = Board::from_fen(fen).expect("bench positions are valid");
+            iterative_deepening(&board, &[], &SearchLimits::depth(depth), &SearchControl::default(), &mut tt).nodes
+        })
+        .sum();
+    BenchResult { nodes, time: start.elapsed() }
+}
+
+/// Search `board` `depth` plies deep with alpha-beta negamax, settling the captures left at the
+/// horizon with a quiescence search, with a transposition table of its own
+pub fn search(board: &Board, depth: u32) -> SearchResult {
+    iterative_deepening(board, &[], &SearchLimits::depth(depth), &SearchControl::default(), &mut TranspositionTable::new(TT_MEGABYTES))
+}
+
+/// Search `board` one ply deeper at a time, trying the best moves of each iteration first in the
+/// next. `earlier_positions` are the repetition hashes of the positions the game went through
+/// before `board`, as `History::earlier_positions` gives them. Once `limits` are reached or
+/// `control` says to stop, the iteration under way is abandoned and the last completed one is
+/// returned; the first iteration always completes. `tt` keeps what earlier searches of the game
+/// learnt, and is left with what this one learns
+pub fn iterative_deepening(board: &Board, earlier_positions: &[u64], limits: &SearchLimits, control: &SearchControl, tt: &mut TranspositionTable) -> SearchResult {
+    let start = Instant::now();
+    // Levels below the maximum, and a random seed for the mistakes they make
+    let weakness = limits.skill_level.map_or(0, |level| MAX_SKILL_LEVEL.saturating_sub(level) as i32);
+    let seed = RandomState::new().build_hasher().finish();
+    let mut searcher = Searcher {
+        nodes: 0,
+        seldepth: 0,
+        control: None,
+        deadline: limits.move_time.filter(|_| !limits.infinite).map(|time| start + time),
+        max_nodes: limits.nodes.filter(|_| !limits.infinite),
+        positions: earlier_positions.iter().copied().chain([board.repetition_hash()]).collect(),
+        aborted: false,
+        history: Box::new([[[0; 64]; 64]; 2]),
+        previous_moves: Vec::new(),
+        countermoves: Box::<Countermoves>::new([[[None; 64]; 7]; 2]),
+        tt,
+        noise: weakness * SKILL_NOISE,
+        seed,
+        contempt: limits.contempt,
+        tablebases: limits.tablebases.as_deref(),
+        params: &limits.params,
+        root_depth: 0,
+    };
+    let mut board = board.clone();
+    let mut moves = generate_all_legal_moves(&board);
+    // The tablebases know which moves keep the best outcome, so only those are searched, for
+    // the quickest way there
+    if let Some(ranked) = limits.tablebases.as_ref().and_then(|tablebases| tablebases.rank_root_moves(&board)) {
+        if let Some(best) = ranked.iter().map(|(_, rank)| *rank).max() {
+            moves = ranked.into_iter().filter(|(_, rank)| *rank == best).map(|(mv, _)| mv).collect();
+        }
+    }
+    let mut result = SearchResult { best_move: None, lines: Vec::new(), score: searcher.terminal_score(&board, 0), depth: 0, nodes: 0 };
+    let mut max_depth = limits.depth.filter(|_| !limits.infinite).unwrap_or(MAX_DEPTH).max(1);
+    let mut multi_pv = limits.multi_pv.max(1);
+    if weakness > 0 {
+        max_depth = max_depth.min(1 + (MAX_SKILL_LEVEL as u32 - weakness as u32) / 2);
+        multi_pv = multi_pv.max(SKILL_CANDIDATES);
+    }
+    'deepening: for depth in 1..=max_depth {
+        // Each line is the best of the moves the lines before it did not start with
+        let mut lines = Vec::new();
+        let mut remaining = moves.clone();
+        while lines.len() < multi_pv && !remaining.is_empty() {
+            let Some((best, score)) = searcher.root(&mut board, &remaining, depth) else {
+                break 'deepening;
+            };
+            let first = remaining.remove(best);
+            lines.push(PvLine { moves: searcher.principal_variation(&board, first, depth), score });
+        }
+        if lines.is_empty() {
+            break;
+        }
+        // The best moves lead the next iteration, which then needs the least searching to beat
+        moves = lines.iter().map(|line| line.moves[0].clone()).chain(remaining).collect();
+        if let Some(info) = &control.info {
+            let time = start.elapsed();
+            // Nobody listening any more is no reason to stop searching
+            let _ = info.send(SearchInfo {
+                depth,
+                seldepth: searcher.seldepth as u32,
+                score: lines[0].score,
+                nodes: searcher.nodes,
+                time,
+                nps: (searcher.nodes as f64 / time.as_secs_f64().max(0.001)) as u64,
+                hashfull: searcher.tt.hashfull(),
+                lines: lines.clone(),
+            });
+        }
+        result = SearchResult { best_move: Some(moves[0].clone()), score: lines[0].score, lines, depth, nodes: searcher.nodes };
+        searcher.control = Some(control);
+        if searcher.should_stop() {
+            break;
+        }
+    }
+    // The result is only wanted once the search is told to stop, or the expected move is played
+    while (limits.infinite || control.is_pondering()) && !control.stop.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    if weakness > 0 && !result.lines.is_empty() {
+        let chosen = pick_line(&result.lines, weakness, seed);
+        let line = result.lines.remove(chosen);
+        result.lines.insert(0, line);
+        result.lines.truncate(limits.multi_pv.max(1));
+        result.best_move = Some(result.lines[0].moves[0].clone());
+        result.score = result.lines[0].score;
+    }
+    result.nodes = searcher.nodes;
+    result
+}
+
+/// A search running on a thread of its own, so the caller can go on, follow it and stop it
+pub struct SearchTask {
+    control: SearchControl,
+    thread: JoinHandle<SearchResult>,
+    info: Receiver<SearchInfo>,
+    latest_info: Option<SearchInfo>,
+}
+
+impl SearchTask {
+    /// Start searching `board` as `iterative_deepening` does, with `tt` once no other search
+    /// holds it
+    pub fn spawn(board: Board, earlier_positions: Vec<u64>, limits: SearchLimits, control: SearchControl, tt: Arc<Mutex<TranspositionTable>>) -> SearchTask {
+        let (sender, info) = std::sync::mpsc::channel();
+        let control = control.with_info(sender);
+        let thread = {
+            let control = control.clone();
+            std::thread::spawn(move || {
+                let mut tt = tt.lock().expect("an earlier search thread panicked");
+                iterative_deepening(&board, &earlier_positions, &limits, &control, &mut tt)
+            })
+        };
+        // Only the search thread holds a sender, so the channel closes when it ends
+        let control = SearchControl { info: None, ..control };
+        SearchTask { control, thread, info, latest_info: None }
+    }
+
+    /// Control of the search, to stop it or signal a ponderhit
+    pub fn control(&self) -> &SearchControl {
+        &self.control
+    }
+
+    /// Progress after the last iteration completed so far
+    pub fn latest_info(&mut self) -> Option<&SearchInfo> {
+        if let Some(info) = self.info.try_iter().last() {
+            self.latest_info = Some(info);
+        }
+        self.latest_info.as_ref()
+    }
+
+    /// Whether the search has ended, so `join` returns at once
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Wait for the search to end and take its result
+    pub fn join(self) -> SearchResult {
+        self.thread.join().expect("search thread panicked")
+    }
+}
+
+/// From and to squares of a quiet reply for each side to reply and each piece and destination of
+/// the move replied to, indexed by `Color`, `Piece` and `Mailbox64Index`
+type Countermoves = [[[Option<(Mailbox64Index, Mailbox64Index)>; 64]; 7]; 2];
+
+/// State of one search across its iterations
+struct Searcher<'a> {
+    nodes: u64,
+    /// Most plies from the root visited
+    seldepth: i32,
+    /// When to give up, left unset until one iteration has completed
+    control: Option<&'a SearchControl>,
+    /// Limits of the search
+    deadline: Option<Instant>,
+    max_nodes: Option<u64>,
+    /// Repetition hashes of the positions of the game and then of the line searched, up to the
+    /// node being searched
+    positions: Vec<u64>,
+    /// The search was told to stop, so scores from the iteration under way are worthless
+    aborted: bool,
+    /// How well each quiet move did before, by color, from square and to square. Raised when the
+    /// move causes a cutoff and lowered when another quiet move does after it was tried
+    history: Box<[[[i32; 64]; 64]; 2]>,
+    /// Piece and destination of each move leading to the node being searched, `None` for a null
+    /// move, and from and to squares of the quiet move that last refuted each piece and
+    /// destination
+    previous_moves: Vec<Option<(Piece, Mailbox64Index)>>,
+    countermoves: Box<Countermoves>,
+    tt: &'a mut TranspositionTable,
+    /// How far in centipawns the evaluation is made to be off at most, to play weaker, and the
+    /// seed of the errors
+    noise: i32,
+    seed: u64,
+    /// How much worse than equal a draw is for the side to move at the root
+    contempt: i32,
+    tablebases: Option<&'a Tablebases>,
+    params: &'a Params,
+    /// Depth of the iteration under way
+    root_depth: u32,
+}
+
+impl Searcher<'_> {
+    /// Index in `moves` and score of the best of the legal `moves` in `board`, searched `depth`
+    /// plies deep, or `None` when the search was abandoned
+    fn root(&mut self, board: &mut Board, moves: &[Move], depth: u32) -> Option<(usize, i32)> {
+        self.root_depth = depth;
+        let mut best = None;
+        let mut best_score = -MATE - 1;
+        for (index, mv) in moves.iter().enumerate() {
+            let undo = board.make_move(mv);
+            self.previous_moves.push(Some((mv.piece, mv.to)));
+            // Only moves beating the best so far matter, so after the first a null window tells
+            // whether they do, and only those that do are searched for their actual score
+            let score = if best.is_none() {
+                -self.negamax(board, depth - 1, 1, -MATE - 1, MATE + 1, true)
+            } else {
+                let score = -self.negamax(board, depth - 1, 1, -best_score - 1, -best_score, true);
+                if score > best_score && !self.aborted {
+                    -self.negamax(board, depth - 1, 1, -MATE - 1, -best_score, true)
+                } else {
+                    score
+                }
+            };
+            self.previous_moves.pop();
+            board.unmake_move(undo);
+            if self.aborted {
+                return None;
+            }
+            if score > best_score {
+                best_score = score;
+                best = Some(index);
+            }
+        }
+        best.map(|best| (best, best_score))
+    }
+
+    /// `first` followed by the best replies the transposition table knows, up to `depth` moves
+    fn principal_variation(&self, board: &Board, first: Move, depth: u32) -> Vec<Move> {
+        let mut board = board.clone();
+        board.make_move(&first);
+        let mut line = vec![first];
+        while line.len() < depth as usize {
+            let Some(mv) = self.tt.probe(board.zobrist()).and_then(|entry| entry.best_move.as_ref()) else {
+                break;
+            };
+            // A different position may share the hash, so the move need not even be legal
+            let Some(mv) = legal_move(&board, mv) else {
+                break;
+            };
+            board.make_move(&mv);
+            line.push(mv);
+        }
+        line
+    }
+
+    /// Score of `board`, `ply` moves from the root, searched `depth` more plies. Fail-soft: a
+    /// score at or below `alpha` or at or above `beta` is only a bound, but may lie beyond it.
+    /// `null_allowed` is false right after a null move and while verifying one
+    fn negamax(&mut self, board: &mut Board, mut depth: u32, ply: i32, mut alpha: i32, beta: i32, null_allowed: bool) -> i32 {
+        let key = board.zobrist();
+        let position = board.repetition_hash_from(key);
+        if self.is_draw(board, position) {
+            return self.draw_score(ply);
+        }
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+        if !self.visit(ply) {
+            return 0;
+        }
+        let (tt_move, tt_score) = match self.tt.probe(key) {
+            Some(entry) => {
+                let score = score_from_tt(entry.score, ply);
+                let usable = entry.depth >= depth && match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if usable {
+                    return score;
+                }
+                // A score at least this good from a search nearly as deep as this one may show
+                // the move to be the only good one
+                let singular = entry.bound != Bound::Upper && entry.depth + 3 >= depth && score.abs() < MATE_BOUND;
+                (entry.best_move.clone(), Some(score).filter(|_| singular))
+            },
+            None => (None, None),
+        };
+        if let Some((score, bound)) = self.probe_tablebases(board, ply) {
+            let usable = match bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if usable {
+                return score;
+            }
+        }
+        // Internal iterative reduction: without a best move to try first the moves are poorly
+        // ordered, so the node is searched cheaply now and better next iteration
+        if tt_move.is_none() && depth as i32 >= self.params.search.iir_min_depth {
+            depth -= 1;
+        }
+        let mut moves = generate_all_legal_moves(board);
+        if moves.is_empty() {
+            return self.terminal_score(board, ply);
+        }
+        if let Some(score) = self.reverse_futility_cutoff(board, depth, alpha, beta) {
+            return score;
+        }
+        if null_allowed && depth as i32 >= self.params.search.null_move_min_depth {
+            if let Some(score) = self.null_move_cutoff(board, depth, ply, beta) {
+                return score;
+            }
+        }
+        // The quiet move that last refuted the move leading here is likely to again
+        let previous = self.previous_moves.last().copied().flatten();
+        let countermove = previous.and_then(|(piece, to)| self.countermoves[board.turn() as usize][piece as usize][to.0 as usize]);
+        self.order_moves(board, &mut moves, tt_move.as_ref(), countermove);
+        // Frontier nodes too far below alpha only get to try moves that change the material
+        // or the king's safety
+        let futility = match self.params.search.futility_margins.get(depth as usize) {
+            Some(margin) if alpha.abs() < MATE_BOUND && !is_in_check(board, board.turn()) => Some(self.evaluate(board) + margin),
+            _ => None,
+        };
+        let original_alpha = alpha;
+        let mut best = -MATE;
+        let mut best_move = None;
+        self.positions.push(position);
+        // Singular extension: when a shallower search of every other move fails low against a
+        // margin below the score of the move from the transposition table, that move is forced
+        // and gets a ply more, up to twice the depth of the iteration
+        let extend_first = match tt_score {
+            Some(score) if depth as i32 >= self.params.search.singular_min_depth && ply < 2 * self.root_depth as i32 && moves.first() == tt_move.as_ref() => {
+                let singular_beta = score - self.params.search.singular_margin * depth as i32;
+                !self.any_reaches(board, &moves[1..], (depth - 1) / 2, ply, singular_beta) && !self.aborted
+            },
+            _ => false,
+        };
+        for (index, mv) in moves.iter().enumerate() {
+            if let Some(futility) = futility.filter(|&futility| futility <= alpha) {
+                if index > 0 && is_quiet(mv) && !mv.gives_check {
+                    best = best.max(futility);
+                    continue;
+                }
+            }
+            let undo = board.make_move(mv);
+            self.previous_moves.push(Some((mv.piece, mv.to)));
+            // Principal variation search: the first move is expected to be best, so the others
+            // only get a null window proving they are not, and a full search when that fails
+            let score = if index == 0 {
+                let child_depth = if extend_first { depth } else { depth - 1 };
+                -self.negamax(board, child_depth, ply + 1, -beta, -alpha, true)
+            } else {
+                let score = -self.negamax(board, depth - 1, ply + 1, -alpha - 1, -alpha, true);
+                if score > alpha && score < beta {
+                    -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, true)
+                } else {
+                    score
+                }
+            };
+            self.previous_moves.pop();
+            board.unmake_move(undo);
+            if score > best {
+                best = score;
+                best_move = Some(index);
+                if score > alpha {
+                    alpha = score;
+                }
+                if score >= beta {
+                    if is_quiet(mv) {
+                        if let Some((piece, to)) = previous {
+                            self.countermoves[board.turn() as usize][piece as usize][to.0 as usize] = Some((mv.from, mv.to));
+                        }
+                        let bonus = (depth * depth) as i32;
+                        self.update_history(board.turn(), mv, bonus);
+                        for tried in moves[..index].iter().filter(|tried| is_quiet(tried)) {
+                            self.update_history(board.turn(), tried, -bonus);
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+        self.positions.pop();
+        if !self.aborted {
+            let bound = if best >= beta {
+                Bound::Lower
+            } else if best > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            self.tt.store(key, depth, score_to_tt(best, ply), bound, best_move.map(|index| moves[index].clone()));
+        }
+        best
+    }
+
+    /// Whether any of `moves` in `board` scores at least `beta`, searched `depth` plies deep
+    fn any_reaches(&mut self, board: &mut Board, moves: &[Move], depth: u32, ply: i32, beta: i32) -> bool {
+        moves.iter().any(|mv| {
+            let undo = board.make_move(mv);
+            self.previous_moves.push(Some((mv.piece, mv.to)));
+            let score = -self.negamax(board, depth, ply + 1, -beta, -beta + 1, true);
+            self.previous_moves.pop();
+            board.unmake_move(undo);
+            score >= beta
+        })
+    }
+
+    /// Score of `board` when the side to move has no legal move: mated `ply` moves from the
+    /// root, or stalemated
+    fn terminal_score(&self, board: &Board, ply: i32) -> i32 {
+        if is_in_check(board, board.turn()) {
+            -MATE + ply
+        } else {
+            self.draw_score(ply)
+        }
+    }
+
+    /// Score of `board` by the tablebases, right after a capture or pawn move with few enough
+    /// pieces left. Wins are only known to be at least as good as the quickest possible one,
+    /// and losses at most as bad
+    fn probe_tablebases(&self, board: &Board, ply: i32) -> Option<(i32, Bound)> {
+        let tablebases = self.tablebases?;
+        let pieces = board.pieces(Color::White).count() + board.pieces(Color::Black).count();
+        if board.halfmove_clock() != 0 || pieces > tablebases.max_pieces() {
+            return None;
+        }
+        Some(match tablebases.probe_wdl(board)? {
+            Wdl::Win => (TB_WIN - ply, Bound::Lower),
+            Wdl::Loss => (-TB_WIN + ply, Bound::Upper),
+            wdl => (self.draw_score(ply) + wdl as i32, Bound::Exact),
+        })
+    }
+
+    /// Score of a draw `ply` moves from the root, for the side to move there
+    fn draw_score(&self, ply: i32) -> i32 {
+        if ply % 2 == 0 {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    /// Static evaluation of `board`, off by the noise of the skill level. The error is the same
+    /// whenever the position comes up again, so the search stays consistent
+    fn evaluate(&self, board: &Board) -> i32 {
+        let score = evaluate_with(board, &self.params.eval);
+        if self.noise == 0 {
+            return score;
+        }
+        let error = mix(board.zobrist() ^ self.seed) % (2 * self.noise as u64 + 1);
+        score + error as i32 - self.noise
+    }
+
+    /// Whether `board`, whose repetition hash is `position`, is a draw by the fifty-move rule or
+    /// repeats a position of the game or of the line searched. Repeating once is enough: if
+    /// going back was best the first time, it is again
+    fn is_draw(&self, board: &Board, position: u64) -> bool {
+        let halfmove_clock = board.halfmove_clock() as usize;
+        if halfmove_clock >= 100 {
+            // Mate on the last move still counts
+            return !(is_in_check(board, board.turn()) && generate_all_legal_moves(board).is_empty());
+        }
+        self.positions.iter().rev().take(halfmove_clock).any(|&earlier| earlier == position)
+    }
+
+    /// Score of `board` once the captures worth making are made, so the evaluation is not taken
+    /// in the middle of an exchange. The side to move may stand pat on the static evaluation
+    /// instead of capturing, unless in check, where every evasion is searched
+    fn quiescence(&mut self, board: &mut Board, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        if !self.visit(ply) {
+            return 0;
+        }
+        if ply >= MAX_PLY {
+            return self.evaluate(board);
+        }
+        let mut moves = generate_all_legal_moves(board);
+        if moves.is_empty() {
+            return self.terminal_score(board, ply);
+        }
+        let in_check = is_in_check(board, board.turn());
+        let mut best = -MATE;
+        if !in_check {
+            best = self.evaluate(board);
+            if best >= beta {
+                return best;
+            }
+            alpha = alpha.max(best);
+            // Captures that lose material are not worth looking at
+            moves.retain(|mv| !is_quiet(mv) && static_exchange(board, mv) >= 0);
+        }
+        self.order_moves(board, &mut moves, None, None);
+        for mv in &moves {
+            let undo = board.make_move(mv);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move(undo);
+            if score > best {
+                best = score;
+                alpha = alpha.max(score);
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// Count a node, and look whether the search should stop every so often. Returns false once
+    /// it has to
+    fn visit(&mut self, ply: i32) -> bool {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        if (self.nodes.is_multiple_of(NODES_BETWEEN_CHECKS) || self.max_nodes == Some(self.nodes)) && self.should_stop() {
+            self.aborted = true;
+        }
+        !self.aborted
+    }
+
+    /// Whether the search has to stop, which it never does before `control` is set
+    fn should_stop(&self) -> bool {
+        self.control.is_some_and(|control| {
+            control.should_stop(self.deadline.as_ref()) || self.max_nodes.is_some_and(|max_nodes| self.nodes >= max_nodes)
+        })
+    }
+
+    /// Score to cut the node off with if passing the turn still leaves the side to move at or
+    /// above `beta`, so a real move can only do better. Passing is no test in check, in reach of
+    /// mate or with only pawns left, where zugzwang is common. Deep cutoffs are verified by a
+    /// reduced search without null moves, to catch the zugzwangs that slip through
+    fn null_move_cutoff(&mut self, board: &mut Board, depth: u32, ply: i32, beta: i32) -> Option<i32> {
+        let turn = board.turn();
+        let has_pieces = board.pieces(turn).any(|square| !matches!(board.piece_at(square), Some((Piece::Pawn | Piece::King, _))));
+        if beta.abs() >= MATE_BOUND || !has_pieces || is_in_check(board, turn) || self.evaluate(board) < beta {
+            return None;
+        }
+        let params = &self.params.search;
+        let reduction = (params.null_move_reduction + depth as i32 / params.null_move_reduction_depth.max(1)).max(0) as u32;
+        let verify = depth as i32 >= params.null_move_verify_depth;
+        let reduced = depth.saturating_sub(1 + reduction);
+        let en_passant_target_square = board.make_null_move();
+        self.previous_moves.push(None);
+        let score = -self.negamax(board, reduced, ply + 1, -beta, -beta + 1, false);
+        self.previous_moves.pop();
+        board.unmake_null_move(en_passant_target_square);
+        if self.aborted || score < beta {
+            return None;
+        }
+        if verify && self.negamax(board, depth.saturating_sub(reduction), ply, beta - 1, beta, false) < beta {
+            return None;
+        }
+        // A mate found after passing proves nothing about the moves that were skipped
+        Some(if score >= MATE_BOUND { beta } else { score })
+    }
+
+    /// Static null move pruning: a node searched with a null window close to the leaves whose
+    /// static evaluation is far enough above beta is assumed to stay there whatever the
+    /// opponent does, unless the side to move is in check or mates are at stake
+    fn reverse_futility_cutoff(&self, board: &Board, depth: u32, alpha: i32, beta: i32) -> Option<i32> {
+        if depth as i32 > self.params.search.reverse_futility_max_depth || beta - alpha > 1 || beta.abs() >= MATE_BOUND || is_in_check(board, board.turn()) {
+            return None;
+        }
+        let eval = self.evaluate(board);
+        (eval - self.params.search.reverse_futility_margin * depth as i32 >= beta).then_some(eval)
+    }
+
+    /// Sort the legal `moves` in `board` so the likeliest to cause a cutoff come first: the best
+    /// move the transposition table knows, captures and promotions that do not lose material,
+    /// the most valuable victim first and then the cheapest attacker, then the `countermove`
+    /// from and to squares and the other quiet moves by history, and the losing captures last
+    fn order_moves(&self, board: &Board, moves: &mut [Move], tt_move: Option<&Move>, countermove: Option<(Mailbox64Index, Mailbox64Index)>) {
+        let color = board.turn() as usize;
+        moves.sort_by_cached_key(|mv| {
+            if Some(mv) == tt_move {
+                (-1, 0)
+            } else if is_quiet(mv) && countermove == Some((mv.from, mv.to)) {
+                (1, i32::MIN)
+            } else if is_quiet(mv) {
+                (1, -self.history[color][mv.from.0 as usize][mv.to.0 as usize])
+            } else {
+                let victim = mv.captured.map_or(0, |piece| piece.value()) + mv.promotion.map_or(0, |piece| piece.value());
+                let group = if static_exchange(board, mv) >= 0 { 0 } else { 2 };
+                (group, mv.piece.value() - 10 * victim)
+            }
+        });
+    }
+
+    /// Move the history score of `mv` by `bonus`, less the closer it already is to the bound so
+    /// scores stay within `HISTORY_MAX`
+    fn update_history(&mut self, color: Color, mv: &Move, bonus: i32) {
+        let entry = &mut self.history[color as usize][mv.from.0 as usize][mv.to.0 as usize];
+        *entry += bonus - *entry * bonus.abs() / HISTORY_MAX;
+    }
+}
+
+/// Index of the line an engine `weakness` levels below full strength plays out of `lines`, best
+/// first: each line gets a random bonus of up to `SKILL_SLACK` per level, and the best total wins
+fn pick_line(lines: &[PvLine], weakness: i32, seed: u64) -> usize {
+    let slack = (weakness * SKILL_SLACK) as u64;
+    (0..lines.len())
+        .max_by_key(|&index| lines[index].score + (mix(seed ^ index as u64) % (slack + 1)) as i32)
+        .unwrap()
+}
+
+/// Scramble `value` into a pseudo-random number, splitmix64 style
+fn mix(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+/// Whether `mv` neither captures nor promotes
+fn is_quiet(mv: &Move) -> bool {
+    mv.captured.is_none() && mv.promotion.is_none()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tt() -> TranspositionTable {
+        TranspositionTable::new(TT_MEGABYTES)
+    }
+
+    #[test]
+    fn finds_mate_and_material() {
+        // Back rank mate in one
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search(&board, 2);
+        assert_eq!(result.best_move.unwrap().to_uci(), "a1a8");
+        assert_eq!(result.score, MATE - 1);
+
+        // A hanging queen
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        assert_eq!(search(&board, 2).best_move.unwrap().to_uci(), "d2d5");
+    }
+
+    #[test]
+    fn multi_pv_lines() {
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = iterative_deepening(&board, &[], &SearchLimits::depth(3).with_multi_pv(3), &SearchControl::default(), &mut tt());
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.lines[0].moves[0].to_uci(), "d2d5");
+        assert!(result.lines[0].score > result.lines[1].score);
+        assert!(result.lines[1].score >= result.lines[2].score);
+        assert!(result.lines[1].moves[0] != result.lines[2].moves[0]);
+    }
+
+    #[test]
+    fn skill_levels() {
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let full = iterative_deepening(&board, &[], &SearchLimits::depth(3).with_skill_level(MAX_SKILL_LEVEL), &SearchControl::default(), &mut tt());
+        assert_eq!(full.best_move.unwrap().to_uci(), "d2d5");
+        assert_eq!(full.lines.len(), 1);
+
+        let weakest = iterative_deepening(&board, &[], &SearchLimits::depth(6).with_skill_level(0), &SearchControl::default(), &mut tt());
+        assert_eq!(weakest.depth, 1);
+        assert_eq!(weakest.lines.len(), 1);
+        assert!(weakest.best_move.is_some_and(|mv| legal_move(&board, &mv).is_some()));
+    }
+
+    #[test]
+    fn bench_is_reproducible() {
+        let first = bench(3);
+        assert!(first.nodes > 0);
+        assert_eq!(bench(3).nodes, first.nodes);
+    }
+
+    #[test]
+    fn table_carries_over_between_searches() {
+        let mut tt = tt();
+        let search = |tt: &mut TranspositionTable| iterative_deepening(&Board::default(), &[], &SearchLimits::depth(5), &SearchControl::default(), tt).nodes;
+        let first = search(&mut tt);
+        assert!(search(&mut tt) < first);
+        tt.clear();
+        assert_eq!(search(&mut tt), first);
+    }
+
+    #[test]
+    fn mate_distance() {
+        // Mate in two: Kb6 takes a7 and b7 from the king, then Rh8 mates
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let result = search(&board, 4);
+        assert_eq!(Score::new(result.score), Score::Mate(2));
+        assert_eq!(Score::new(-MATE + 4).to_string(), "#-2");
+        assert_eq!(Score::new(-MATE + 2).to_uci(), "mate -1");
+        assert_eq!(Score::new(35).to_uci(), "cp 35");
+    }
+
+    #[test]
+    fn tablebase_wins_are_not_mates() {
+        assert_eq!(Score::new(TB_WIN - 3), Score::Centipawns(TB_WIN_CENTIPAWNS - 3));
+        assert_eq!(Score::new(-TB_WIN + 4).to_uci(), format!("cp {}", -TB_WIN_CENTIPAWNS + 4));
+        assert_eq!(Score::new(TB_WIN_BOUND - 1), Score::Centipawns(TB_WIN_BOUND - 1));
+        // Stored relative to the position, like mates
+        assert_eq!(score_to_tt(TB_WIN - 5, 3), TB_WIN - 2);
+        assert_eq!(score_from_tt(score_to_tt(-TB_WIN + 5, 3), 7), -TB_WIN + 9);
+        // Taking the pawn leaves a KQvK win, which reports a big advantage but no mate. Only with
+        // the tables in the directory `WHALE_SYZYGY_PATH` names
+        let Ok(path) = std::env::var("WHALE_SYZYGY_PATH") else {
+            return;
+        };
+        let limits = SearchLimits { tablebases: Some(Arc::new(Tablebases::open(&path))), ..SearchLimits::depth(4) };
+        let board = Board::from_fen("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let result = iterative_deepening(&board, &[], &limits, &SearchControl::default(), &mut tt());
+        let Score::Centipawns(centipawns) = Score::new(result.score) else {
+            panic!("tablebase win reported as a mate");
+        };
+        assert!(centipawns > TB_WIN_CENTIPAWNS - MAX_PLY && centipawns <= TB_WIN_CENTIPAWNS);
+    }
+
+    #[test]
+    fn draws_by_repetition_and_fifty_moves() {
+        let board = Board::from_fen("3qk3/8/8/8/8/8/8/4K1N1 w - - 4 10").unwrap();
+        let mut repeated = board.clone();
+        repeated.make_move(&Move::from_uci(&board, "g1f3").unwrap());
+        let result = iterative_deepening(&board, &[repeated.repetition_hash()], &SearchLimits::depth(3), &SearchControl::default(), &mut tt());
+        assert_eq!(result.best_move.unwrap().to_uci(), "g1f3");
+        assert_eq!(result.score, 0);
+        // Enough contempt makes even a queen down look better than a draw
+        let limits = SearchLimits { contempt: 2000, ..SearchLimits::depth(3) };
+        let result = iterative_deepening(&board, &[repeated.repetition_hash()], &limits, &SearchControl::default(), &mut tt());
+        assert!(result.best_move.unwrap().to_uci() != "g1f3");
+
+        let board = Board::from_fen("7k/8/8/8/8/8/8/KQ6 w - - 99 80").unwrap();
+        assert_eq!(search(&board, 3).score, 0);
+    }
+
+    #[test]
+    fn stopping_keeps_completed_iteration() {
+        let result = iterative_deepening(&Board::default(), &[], &SearchLimits::move_time(Duration::from_millis(50)), &SearchControl::default(), &mut tt());
+        assert!(result.best_move.is_some());
+        assert!(result.depth >= 1 && result.depth < MAX_DEPTH);
+
+        let control = SearchControl::default();
+        control.stop();
+        let result = iterative_deepening(&Board::default(), &[], &SearchLimits::default(), &control, &mut tt());
+        assert_eq!(result.depth, 1);
+        assert!(result.best_move.is_some());
+
+        let result = iterative_deepening(&Board::default(), &[], &SearchLimits::nodes(20_000), &SearchControl::default(), &mut tt());
+        assert!(result.nodes < 21_000);
+        assert!(result.depth < MAX_DEPTH);
+
+        // Infinite searches wait to be stopped, even with nothing to search
+        let control = SearchControl::default();
+        let search = {
+            let control = control.clone();
+            let mated = Board::from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 0 1").unwrap();
+            std::thread::spawn(move || iterative_deepening(&mated, &[], &SearchLimits::infinite(), &control, &mut tt()))
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!search.is_finished());
+        control.stop();
+        assert_eq!(search.join().unwrap().depth, 0);
+    }
+
+    #[test]
+    fn pondering_waits_for_ponderhit() {
+        let control = SearchControl::pondering();
+        let search = {
+            let control = control.clone();
+            std::thread::spawn(move || iterative_deepening(&Board::default(), &[], &SearchLimits::default(), &control, &mut tt()))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(control.is_pondering());
+        assert!(!search.is_finished());
+        control.ponderhit(Duration::from_millis(10));
+        assert!(!control.is_pondering());
+        let result = search.join().unwrap();
+        assert!(result.depth < MAX_DEPTH);
+        assert!(result.ponder_move().is_some());
+    }
+
+    #[test]
+    fn search_on_a_thread() {
+        let task = SearchTask::spawn(Board::default(), Vec::new(), SearchLimits::infinite(), SearchControl::default(), Arc::new(Mutex::new(tt())));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!task.is_finished());
+        task.control().stop();
+        let result = task.join();
+        assert!(result.best_move.is_some());
+
+        let mut task = SearchTask::spawn(Board::default(), Vec::new(), SearchLimits::depth(2), SearchControl::default(), Arc::new(Mutex::new(tt())));
+        while !task.is_finished() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(task.latest_info().map(|info| info.depth), Some(2));
+        assert_eq!(task.join().depth, 2);
+    }
+
+    #[test]
+    fn info_after_each_iteration() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let control = SearchControl::default().with_info(sender);
+        let result = iterative_deepening(&Board::default(), &[], &SearchLimits::depth(3).with_multi_pv(2), &control, &mut tt());
+        drop(control);
+        let infos: Vec<SearchInfo> = receiver.iter().collect();
+        assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<_>>(), [1, 2, 3]);
+        let last = infos.last().unwrap();
+        assert!(last.seldepth >= 3);
+        assert_eq!(last.nodes, result.nodes);
+        let uci = last.to_uci();
+        assert_eq!(uci.len(), 2);
+        assert!(uci[1].starts_with("info depth 3 seldepth ") && uci[1].contains(" multipv 2 score cp "));
+    }
+
+    #[test]
+    fn time_allocation_keeps_a_margin() {
+        assert_eq!(allocate_time(Duration::from_millis(3050), Duration::ZERO, None), Duration::from_millis(100));
+        assert_eq!(allocate_time(Duration::from_millis(3050), Duration::from_secs(2), Some(1)), Duration::from_secs(3));
+        assert_eq!(allocate_time(Duration::from_millis(20), Duration::from_secs(1), None), Duration::ZERO);
+    }
+}
@@ -0,0 +1,1058 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Neg;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use once_cell::sync::Lazy;
+use crate::chess_parts::*;
+use crate::chess_engine::*;
+
+/// Most pieces, kings included, a Syzygy table can hold
+const MAX_PIECES: usize = 7;
+
+/// First bytes of WDL and DTZ files
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+/// Flags of the tables in a file
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// Rank of a root move winning within the fifty-move rule, see `Tablebases::rank_root_moves`
+pub const MAX_DTZ: i32 = 1 << 18;
+
+/// Outcome of a position with perfect play, for the side to move. A cursed win is a win the
+/// fifty-move rule turns into a draw, a blessed loss a loss it saves
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    /// Lost
+    Loss = -2,
+    /// Lost, but drawn by the fifty-move rule
+    BlessedLoss = -1,
+    /// Drawn
+    Draw = 0,
+    /// Won, but drawn by the fifty-move rule
+    CursedWin = 1,
+    /// Won
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Option<Wdl> {
+        match value {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None,
+        }
+    }
+}
+
+/// The outcome for the other side
+impl Neg for Wdl {
+    type Output = Wdl;
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-(self as i32)).unwrap()
+    }
+}
+
+/// Distance to zeroing of the move before a capture or pawn move leading to `wdl`, which DTZ
+/// tables do not store
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+/// Index tables shared by all files, squares numbered from a1 as the files do
+struct Indexing {
+    /// Squares below the a1-h8 diagonal, numbered 0..28
+    b1_h1_h7: [usize; 64],
+    /// Squares of the a1-d1-d4 triangle below the diagonal numbered 0..6, then the diagonal
+    a1_d1_d4: [usize; 64],
+    /// The 462 ways to place two kings with the first in the a1-d1-d4 triangle
+    kk: [[usize; 64]; 10],
+    /// Binomial coefficients: `binomial[k][n]` ways to pick `k` of `n`
+    binomial: [[u64; 64]; MAX_PIECES],
+    /// Pawn squares a2-h7 numbered so the leading pawn, nearest the edge and then lowest, is
+    /// highest
+    pawns: [usize; 64],
+    /// Start index of a leading pawn square, and size of all leading pawn indices, by number of
+    /// leading pawns and file
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+static INDEXING: Lazy<Indexing> = Lazy::new(|| {
+    let mut indexing = Indexing {
+        b1_h1_h7: [0; 64],
+        a1_d1_d4: [0; 64],
+        kk: [[0; 64]; 10],
+        binomial: [[0; 64]; MAX_PIECES],
+        pawns: [0; 64],
+        lead_pawn_idx: [[0; 64]; 6],
+        lead_pawns_size: [[0; 4]; 6],
+    };
+    let mut code = 0;
+    for square in 0..64 {
+        if off_diagonal(square) < 0 {
+            indexing.b1_h1_h7[square] = code;
+            code += 1;
+        }
+    }
+    let mut diagonal = Vec::new();
+    code = 0;
+    for square in 0..28 {
+        if off_diagonal(square) < 0 && square % 8 <= 3 {
+            indexing.a1_d1_d4[square] = code;
+            code += 1;
+        } else if off_diagonal(square) == 0 && square % 8 <= 3 {
+            diagonal.push(square);
+        }
+    }
+    for square in diagonal {
+        indexing.a1_d1_d4[square] = code;
+        code += 1;
+    }
+    // With the first king on the diagonal the second is kept below it, and positions with
+    // both on the diagonal come last
+    let mut both_on_diagonal = Vec::new();
+    code = 0;
+    for index in 0..10 {
+        for first in 0..28 {
+            // Squares outside the triangle map to 0 as well, and b1 is the one that does
+            if indexing.a1_d1_d4[first] != index || (index == 0 && first != 1) {
+                continue;
+            }
+            for second in 0..64 {
+                if kings_touch(first, second) || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                    continue;
+                }
+                if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                    both_on_diagonal.push((index, second));
+                } else {
+                    indexing.kk[index][second] = code;
+                    code += 1;
+                }
+            }
+        }
+    }
+    for (index, second) in both_on_diagonal {
+        indexing.kk[index][second] = code;
+        code += 1;
+    }
+    indexing.binomial[0][0] = 1;
+    for n in 1..64 {
+        for k in 0..MAX_PIECES.min(n + 1) {
+            indexing.binomial[k][n] = if k > 0 { indexing.binomial[k - 1][n - 1] } else { 0 }
+                + if k < n { indexing.binomial[k][n - 1] } else { 0 };
+        }
+    }
+    let mut available = 47;
+    for lead_pawns in 1..6 {
+        for file in 0..4 {
+            let mut index = 0;
+            for rank in 1..7 {
+                let square = rank * 8 + file;
+                if lead_pawns == 1 {
+                    indexing.pawns[square] = available;
+                    indexing.pawns[square ^ 7] = available - 1;
+                    available = available.saturating_sub(2);
+                }
+                indexing.lead_pawn_idx[lead_pawns][square] = index;
+                index += indexing.binomial[lead_pawns - 1][indexing.pawns[square]];
+            }
+            indexing.lead_pawns_size[lead_pawns][file] = index;
+        }
+    }
+    indexing
+});
+
+/// How far above the a1-h8 diagonal `square` is, negative below it
+fn off_diagonal(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+/// `square` mirrored in the a1-h8 diagonal
+fn flip_diagonal(square: usize) -> usize {
+    ((square >> 3) | (square << 3)) & 63
+}
+
+fn kings_touch(first: usize, second: usize) -> bool {
+    (first / 8).abs_diff(second / 8) <= 1 && (first % 8).abs_diff(second % 8) <= 1
+}
+
+/// Syzygy code of a piece: 1 to 6 from pawn to king, plus 8 for Black
+fn piece_code(piece: Piece, color: Color) -> u8 {
+    let code = match piece {
+        Piece::Pawn => 1,
+        Piece::Knight => 2,
+        Piece::Bishop => 3,
+        Piece::Rook => 4,
+        Piece::Queen => 5,
+        Piece::King => 6,
+    };
+    if color == Color::White { code } else { code + 8 }
+}
+
+/// Material of `board` as tables are named, like "KRPvKR", with White's pieces first
+fn material(board: &Board) -> String {
+    let side = |color: Color| {
+        let mut pieces: Vec<Piece> = board.pieces(color).filter_map(|square| board.piece_at(square)).map(|(piece, _)| piece).collect();
+        pieces.sort_by_key(|&piece| std::cmp::Reverse(piece_code(piece, Color::White)));
+        pieces.into_iter().map(|piece| piece.to_char()).collect::<String>()
+    };
+    format!("{}v{}", side(Color::White), side(Color::Black))
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// What a table's name says about its positions
+#[derive(Clone, Debug)]
+struct TableInfo {
+    /// Material with the side the table is computed for first, like "KRvK"
+    name: String,
+    pieces: usize,
+    has_pawns: bool,
+    /// Some side has a piece other than the king only once
+    has_unique_pieces: bool,
+    /// Pawns of the leading color, which has the fewer if both have some, and of the other
+    pawn_counts: [usize; 2],
+    /// Both sides have the same pieces
+    symmetric: bool,
+}
+
+impl TableInfo {
+    fn from_name(name: &str) -> Option<TableInfo> {
+        let (white, black) = name.split_once('v')?;
+        let valid = |side: &str| side.starts_with('K') && side.matches('K').count() == 1 && side.chars().all(|c| "KQRBNP".contains(c));
+        if !valid(white) || !valid(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+        let unique = |side: &str| side.chars().any(|c| c != 'K' && side.matches(c).count() == 1);
+        let (white_pawns, black_pawns) = (white.matches('P').count(), black.matches('P').count());
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(TableInfo {
+            name: name.to_string(),
+            pieces: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: unique(white) || unique(black),
+            pawn_counts: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            symmetric: white == black,
+        })
+    }
+}
+
+/// Decoding information of one table in a file: one side to move and, with pawns, one file of
+/// the leading pawn. Positions are the offsets in the file
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    /// Shortest Huffman symbol in bits, or the value of every position of a single value table
+    min_sym_len: u8,
+    block_size: usize,
+    /// There is a sparse index entry every `span` values
+    span: usize,
+    sparse_index_size: usize,
+    /// Blocks, and entries of the block length table, padded beyond the blocks
+    blocks: usize,
+    block_length_size: usize,
+    lowest_sym: usize,
+    btree: usize,
+    sparse_index: usize,
+    block_length: usize,
+    data: usize,
+    /// Lowest symbol of each length, left-aligned in 64 bits
+    base64: Vec<u64>,
+    /// Values less one each symbol expands to
+    symlen: Vec<u16>,
+    /// Pieces in the order the table encodes them
+    pieces: [u8; MAX_PIECES],
+    /// Pieces encoded together, and the factor of their index
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    /// Start of the DTZ value map of each outcome
+    map_idx: [u16; 4],
+}
+
+impl PairsData {
+    /// Symbols `symbol` expands to, left and right
+    fn children(&self, data: &[u8], symbol: usize) -> Option<(usize, usize)> {
+        let lr = data.get(self.btree + 3 * symbol..self.btree + 3 * symbol + 3)?;
+        let left = ((lr[1] as usize & 0xf) << 8) | lr[0] as usize;
+        let right = ((lr[2] as usize) << 4) | (lr[1] as usize >> 4);
+        Some((left, right))
+    }
+
+    /// Split the pieces into groups encoded together and work out the index factor of each,
+    /// in the `order` the file gives
+    fn set_groups(&mut self, info: &TableInfo, order: [u8; 2], file: usize) {
+        let mut n = 0;
+        let mut first_len: i32 = if info.has_pawns { 0 } else if info.has_unique_pieces { 3 } else { 2 };
+        self.group_len[0] = 1;
+        for i in 1..info.pieces {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                self.group_len[n] += 1;
+            } else {
+                n += 1;
+                self.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        self.group_len[n] = 0;
+        let both_have_pawns = info.has_pawns && info.pawn_counts[1] > 0;
+        let mut next = if both_have_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - self.group_len[0] - if both_have_pawns { self.group_len[1] } else { 0 };
+        let mut index = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                self.group_idx[0] = index;
+                index *= if info.has_pawns {
+                    INDEXING.lead_pawns_size[self.group_len[0]][file]
+                } else if info.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                self.group_idx[1] = index;
+                index *= INDEXING.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_idx[next] = index;
+                index *= INDEXING.binomial[self.group_len[next]][free_squares];
+                free_squares -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_idx[n] = index;
+    }
+
+    /// Read the sizes and Huffman code starting at `at`, returning where they end
+    fn set_sizes(&mut self, data: &[u8], mut at: usize) -> Option<usize> {
+        self.flags = *data.get(at)?;
+        at += 1;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            self.min_sym_len = *data.get(at)?;
+            return Some(at + 1);
+        }
+        let groups = self.group_len.iter().position(|&len| len == 0)?;
+        let size = self.group_idx[groups];
+        self.block_size = 1 << *data.get(at)?;
+        self.span = 1 << *data.get(at + 1)?;
+        self.sparse_index_size = size.div_ceil(self.span as u64) as usize;
+        let padding = *data.get(at + 2)? as usize;
+        self.blocks = read_u32(data, at + 3)? as usize;
+        self.block_length_size = self.blocks + padding;
+        let max_sym_len = *data.get(at + 7)?;
+        self.min_sym_len = *data.get(at + 8)?;
+        at += 9;
+        self.lowest_sym = at;
+        let lengths = (max_sym_len.checked_sub(self.min_sym_len)? as usize) + 1;
+        // Canonical Huffman code: longer symbols have lower values, so the lowest symbol of each
+        // length, padded to 64 bits, is at least the lowest of the next length
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16(data, self.lowest_sym + 2 * i)? as u64;
+            let next_lowest = read_u16(data, self.lowest_sym + 2 * i + 2)? as u64;
+            self.base64[i] = self.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base.checked_shl((64 - i - self.min_sym_len as usize) as u32).unwrap_or(0);
+        }
+        at += 2 * lengths;
+        let symbols = read_u16(data, at)? as usize;
+        at += 2;
+        self.btree = at;
+        // Recursive pairing: each symbol stands for a pair of other symbols, down to the values
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                self.symlen[symbol] = self.set_symlen(data, symbol, &mut visited)?;
+            }
+        }
+        Some(at + 3 * symbols + (symbols & 1))
+    }
+
+    fn set_symlen(&mut self, data: &[u8], symbol: usize, visited: &mut [bool]) -> Option<u16> {
+        visited[symbol] = true;
+        let (left, right) = self.children(data, symbol)?;
+        if right == 0xfff {
+            return Some(0);
+        }
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                self.symlen[child] = self.set_symlen(data, child, visited)?;
+            }
+        }
+        Some(self.symlen[left] + self.symlen[right] + 1)
+    }
+
+    /// Value stored at `index`
+    fn decompress(&self, data: &[u8], index: u64) -> Option<usize> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len as usize);
+        }
+        // The sparse index gives the block and offset of every span-th value from the middle of
+        // the span, from which the block lengths lead to the block holding `index`
+        let entry = self.sparse_index + 6 * (index / self.span as u64) as usize;
+        let mut block = read_u32(data, entry)? as usize;
+        let mut offset = read_u16(data, entry + 4)? as i64 + (index % self.span as u64) as i64 - (self.span / 2) as i64;
+        let block_length = |block: usize| read_u16(data, self.block_length + 2 * block).map(i64::from);
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+        // Read Huffman symbols through the block until the one covering the offset
+        let mut at = self.data + block * self.block_size;
+        let mut buffer = read_u64_be(data, at)?;
+        at += 8;
+        let mut buffer_bits = 64;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while buffer < *self.base64.get(len)? {
+                len += 1;
+            }
+            let shift = (64 - len - self.min_sym_len as usize) as u32;
+            symbol = ((buffer - self.base64[len]).checked_shr(shift).unwrap_or(0) + read_u16(data, self.lowest_sym + 2 * len)? as u64) as usize;
+            let values = *self.symlen.get(symbol)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            len += self.min_sym_len as usize;
+            buffer = buffer.checked_shl(len as u32).unwrap_or(0);
+            buffer_bits -= len as i32;
+            if buffer_bits <= 32 {
+                buffer_bits += 32;
+                buffer |= (read_u32_be(data, at)? as u64) << (64 - buffer_bits);
+                at += 4;
+            }
+        }
+        // Then expand the symbol down to the single value at the offset
+        while self.symlen[symbol] != 0 {
+            let (left, right) = self.children(data, symbol)?;
+            let left_values = *self.symlen.get(left)? as i64 + 1;
+            if offset < left_values {
+                symbol = left;
+            } else {
+                offset -= left_values;
+                symbol = right;
+            }
+        }
+        self.children(data, symbol).map(|(left, _)| left)
+    }
+}
+
+/// A WDL or DTZ file read into memory
+struct TableData {
+    bytes: Vec<u8>,
+    /// Decoding information by side to move, then leading pawn file
+    pairs: Vec<PairsData>,
+    sides: usize,
+    files: usize,
+    /// Start of the DTZ value maps
+    map: usize,
+}
+
+impl TableData {
+    fn read(path: &Path, info: &TableInfo, dtz: bool) -> Option<TableData> {
+        let bytes = std::fs::read(path).ok()?;
+        if bytes.get(..4)? != if dtz { DTZ_MAGIC } else { WDL_MAGIC } {
+            return None;
+        }
+        if (*bytes.get(4)? & 2 != 0) != info.has_pawns {
+            return None;
+        }
+        // WDL tables of different material store both sides to move, DTZ tables only one
+        let sides = if !dtz && !info.symmetric { 2 } else { 1 };
+        let files = if info.has_pawns { 4 } else { 1 };
+        let both_have_pawns = info.has_pawns && info.pawn_counts[1] > 0;
+        let mut pairs = vec![PairsData::default(); sides * files];
+        let mut at = 5;
+        for file in 0..files {
+            let first = *bytes.get(at)?;
+            let second = if both_have_pawns { *bytes.get(at + 1)? } else { 0xff };
+            let orders = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            at += 1 + both_have_pawns as usize;
+            for k in 0..info.pieces {
+                let byte = *bytes.get(at)?;
+                for side in 0..sides {
+                    pairs[side * files + file].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xf };
+                }
+                at += 1;
+            }
+            for (side, order) in orders.into_iter().enumerate().take(sides) {
+                pairs[side * files + file].set_groups(info, order, file);
+            }
+        }
+        at += at & 1;
+        for file in 0..files {
+            for side in 0..sides {
+                at = pairs[side * files + file].set_sizes(&bytes, at)?;
+            }
+        }
+        let map = at;
+        if dtz {
+            for pairs in pairs.iter_mut() {
+                if pairs.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if pairs.flags & FLAG_WIDE != 0 {
+                    at += at & 1;
+                    for map_idx in pairs.map_idx.iter_mut() {
+                        *map_idx = ((at - map) / 2 + 1) as u16;
+                        at += 2 * read_u16(&bytes, at)? as usize + 2;
+                    }
+                } else {
+                    for map_idx in pairs.map_idx.iter_mut() {
+                        *map_idx = (at - map + 1) as u16;
+                        at += *bytes.get(at)? as usize + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut pairs[side * files + file];
+                pairs.sparse_index = at;
+                at += 6 * pairs.sparse_index_size;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut pairs[side * files + file];
+                pairs.block_length = at;
+                at += 2 * pairs.block_length_size;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut pairs[side * files + file];
+                at = (at + 63) & !63;
+                pairs.data = at;
+                at += pairs.blocks * pairs.block_size;
+            }
+        }
+        (at <= bytes.len()).then_some(TableData { bytes, pairs, sides, files, map })
+    }
+
+    fn pairs(&self, side: usize, file: usize) -> &PairsData {
+        &self.pairs[(side % self.sides) * self.files + file % self.files]
+    }
+
+    /// Distance to zeroing in plies of a DTZ `value` for a position that is a `wdl`
+    fn map_score(&self, file: usize, value: usize, wdl: Wdl) -> Option<i32> {
+        let pairs = self.pairs(0, file);
+        let mut value = value;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let map = pairs.map_idx[match wdl {
+                Wdl::Loss => 1,
+                Wdl::BlessedLoss => 3,
+                Wdl::CursedWin => 2,
+                Wdl::Win | Wdl::Draw => 0,
+            }] as usize;
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16(&self.bytes, self.map + 2 * (map + value))? as usize
+            } else {
+                *self.bytes.get(self.map + map + value)? as usize
+            };
+        }
+        // Tables store moves rather than plies where that makes no difference
+        let in_moves = match wdl {
+            Wdl::Win => pairs.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => pairs.flags & FLAG_LOSS_PLIES == 0,
+            _ => true,
+        };
+        Some(if in_moves { value as i32 * 2 } else { value as i32 } + 1)
+    }
+}
+
+/// A table file pair, read at first use
+struct Table {
+    info: TableInfo,
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<TableData>>,
+    dtz: OnceLock<Option<TableData>>,
+}
+
+impl Table {
+    fn data(&self, dtz: bool) -> Option<&TableData> {
+        if dtz {
+            let path = self.dtz_path.as_deref()?;
+            self.dtz.get_or_init(|| TableData::read(path, &self.info, true)).as_ref()
+        } else {
+            self.wdl.get_or_init(|| TableData::read(&self.wdl_path, &self.info, false)).as_ref()
+        }
+    }
+}
+
+/// What probing a single table gave
+enum TableValue {
+    Value(i32),
+    /// A DTZ table only stores the other side to move
+    ChangeSideToMove,
+}
+
+/// Syzygy endgame tablebases: the WDL tables telling whether a position is won, drawn or lost
+/// with perfect play, and the DTZ tables telling how far the next capture or pawn move is
+pub struct Tablebases {
+    tables: Vec<Table>,
+    /// Index in `tables` by material, for both colors
+    by_material: HashMap<String, usize>,
+    max_pieces: usize,
+}
+
+/// How many tables there are, not what is in them
+impl fmt::Debug for Tablebases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tablebases").field("tables", &self.tables.len()).field("max_pieces", &self.max_pieces).finish()
+    }
+}
+
+impl Tablebases {
+    /// Tablebases in the directories of `path`, separated like in the `PATH` variable. Files are
+    /// only read once probed; missing directories and unknown files are skipped
+    pub fn open(path: &str) -> Tablebases {
+        let mut tablebases = Tablebases { tables: Vec::new(), by_material: HashMap::new(), max_pieces: 0 };
+        for directory in std::env::split_paths(path) {
+            let Ok(entries) = std::fs::read_dir(&directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let wdl_path = entry.path();
+                if wdl_path.extension().is_none_or(|extension| extension != "rtbw") {
+                    continue;
+                }
+                let Some(info) = wdl_path.file_stem().and_then(|stem| stem.to_str()).and_then(TableInfo::from_name) else {
+                    continue;
+                };
+                if tablebases.by_material.contains_key(&info.name) {
+                    continue;
+                }
+                let dtz_path = Some(wdl_path.with_extension("rtbz")).filter(|path| path.exists());
+                let (white, black) = info.name.split_once('v').unwrap();
+                tablebases.by_material.insert(info.name.clone(), tablebases.tables.len());
+                tablebases.by_material.insert(format!("{}v{}", black, white), tablebases.tables.len());
+                tablebases.max_pieces = tablebases.max_pieces.max(info.pieces);
+                tablebases.tables.push(Table { info, wdl_path, dtz_path, wdl: OnceLock::new(), dtz: OnceLock::new() });
+            }
+        }
+        tablebases
+    }
+
+    /// Number of tables found
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Whether no table was found
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Most pieces, kings included, of any table
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether the tables may know `board`: few enough pieces and no castling rights
+    fn covers(&self, board: &Board) -> bool {
+        let pieces = board.pieces(Color::White).count() + board.pieces(Color::Black).count();
+        (pieces <= self.max_pieces || pieces == 2) && board.castling_availability().iter().all(Option::is_none)
+    }
+
+    /// Outcome of `board` with perfect play, if the tables know it
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        if !self.covers(board) {
+            return None;
+        }
+        self.search(board, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Plies to the next capture or pawn move with perfect play, positive when the side to move
+    /// wins and negative when it loses, or 0 in a draw. Give or take a ply, and beyond 100 for
+    /// cursed wins and blessed losses
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        if !self.covers(board) {
+            return None;
+        }
+        self.dtz(board)
+    }
+
+    /// The legal moves of `board` with a rank by what the tables say, higher being better:
+    /// `MAX_DTZ` for wins within the fifty-move rule, lower for wins it may turn into draws,
+    /// 0 for draws and below for losses, the quickest lowest
+    pub fn rank_root_moves(&self, board: &Board) -> Option<Vec<(Move, i32)>> {
+        if !self.covers(board) {
+            return None;
+        }
+        let halfmove_clock = board.halfmove_clock() as i32;
+        generate_all_legal_moves(board)
+            .into_iter()
+            .map(|mv| {
+                let mut after = board.clone();
+                after.make_move(&mv);
+                let mut dtz = if after.halfmove_clock() == 0 {
+                    dtz_before_zeroing(-self.search(&after, false)?.0)
+                } else {
+                    let dtz = -self.dtz(&after)?;
+                    dtz + dtz.signum()
+                };
+                if dtz == 2 && is_in_check(&after, after.turn()) && generate_all_legal_moves(&after).is_empty() {
+                    dtz = 1;
+                }
+                let rank = match dtz {
+                    0 => 0,
+                    dtz if dtz > 0 && dtz + halfmove_clock <= 99 => MAX_DTZ,
+                    dtz if dtz > 0 => MAX_DTZ - (dtz + halfmove_clock),
+                    dtz if -dtz * 2 + halfmove_clock < 100 => -MAX_DTZ,
+                    dtz => -MAX_DTZ + (-dtz + halfmove_clock),
+                };
+                Some((mv, rank))
+            })
+            .collect()
+    }
+
+    /// Outcome of `board`, trying the captures first, and pawn moves too if `zeroing_moves`, and
+    /// whether one of those is the best move. Tables may store anything for positions where a
+    /// capture wins, so those have to be searched
+    fn search(&self, board: &Board, zeroing_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = generate_all_legal_moves(board);
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for mv in &moves {
+            if mv.captured.is_none() && !(zeroing_moves && mv.piece == Piece::Pawn) {
+                continue;
+            }
+            searched += 1;
+            let mut after = board.clone();
+            after.make_move(mv);
+            let value = -self.search(&after, false)?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+        // With every move searched the table is not needed, and may be wrong, as with en passant
+        let no_more_moves = searched > 0 && searched == moves.len();
+        let value = if no_more_moves {
+            best
+        } else {
+            match self.probe_table(board, false, Wdl::Draw)? {
+                TableValue::Value(value) => Wdl::from_value(value)?,
+                TableValue::ChangeSideToMove => return None,
+            }
+        };
+        if best >= value {
+            return Some((best, best > Wdl::Draw || no_more_moves));
+        }
+        Some((value, false))
+    }
+
+    fn dtz(&self, board: &Board) -> Option<i32> {
+        let (wdl, zeroing) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(dtz_before_zeroing(wdl));
+        }
+        let sign = (wdl as i32).signum();
+        match self.probe_table(board, true, wdl)? {
+            TableValue::Value(dtz) => {
+                let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+                Some((dtz + if cursed { 100 } else { 0 }) * sign)
+            },
+            TableValue::ChangeSideToMove => {
+                // Look one ply further for the best move by the other side's table
+                let mut best = 0xffff;
+                for mv in generate_all_legal_moves(board) {
+                    let zeroing = mv.captured.is_some() || mv.piece == Piece::Pawn;
+                    let mut after = board.clone();
+                    after.make_move(&mv);
+                    let mut dtz = if zeroing {
+                        -dtz_before_zeroing(self.search(&after, false)?.0)
+                    } else {
+                        -self.dtz(&after)?
+                    };
+                    if dtz == 1 && is_in_check(&after, after.turn()) && generate_all_legal_moves(&after).is_empty() {
+                        best = 1;
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < best && dtz.signum() == sign {
+                        best = dtz;
+                    }
+                }
+                Some(if best == 0xffff { -1 } else { best })
+            },
+        }
+    }
+
+    /// Value the WDL or DTZ table of the material of `board` holds for it. DTZ values are
+    /// mapped for a position that is a `wdl`
+    fn probe_table(&self, board: &Board, dtz: bool, wdl: Wdl) -> Option<TableValue> {
+        let count = board.pieces(Color::White).count() + board.pieces(Color::Black).count();
+        if count == 2 {
+            return Some(TableValue::Value(0));
+        }
+        let material = material(board);
+        let table = &self.tables[*self.by_material.get(&material)?];
+        let info = &table.info;
+        let data = table.data(dtz)?;
+        let white_to_move = board.turn() == Color::White;
+        // Tables are computed with their first side as White, and symmetric ones with White to
+        // move, so other positions get their colors swapped and board flipped
+        let flip = material != info.name || (info.symmetric && !white_to_move);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let side = (flip == white_to_move) as usize;
+        // Squares numbered from a1, where the board numbers them from a8
+        let squares_of = |color: Color| board.pieces(color).map(|square| square.0 as usize ^ 56);
+        let mut squares = [0; MAX_PIECES];
+        let mut pieces = [0; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        let mut lead_color = None;
+        if info.has_pawns {
+            // Pawns of the color the table starts with lead, and the one nearest the edge
+            // picks which of the four tables of the file holds the position
+            let code = data.pairs(0, 0).pieces[0] ^ flip_color;
+            let color = if code & 8 == 0 { Color::White } else { Color::Black };
+            for square in squares_of(color) {
+                if board.piece_at(Mailbox64Index((square ^ 56) as u8)).map(|(piece, _)| piece) == Some(Piece::Pawn) {
+                    squares[size] = square ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_pawns = size;
+            let leading = (0..lead_pawns).max_by_key(|&i| INDEXING.pawns[squares[i]])?;
+            squares.swap(0, leading);
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+            lead_color = Some(color);
+        }
+        if dtz {
+            let flags = data.pairs(side, file).flags;
+            if (flags & FLAG_STM) as usize != side && (info.has_pawns || !info.symmetric) {
+                return Some(TableValue::ChangeSideToMove);
+            }
+        }
+        for color in [Color::White, Color::Black] {
+            for square in squares_of(color) {
+                let (piece, _) = board.piece_at(Mailbox64Index((square ^ 56) as u8))?;
+                if lead_color == Some(color) && piece == Piece::Pawn {
+                    continue;
+                }
+                *squares.get_mut(size)? = square ^ flip_squares;
+                pieces[size] = piece_code(piece, color) ^ flip_color;
+                size += 1;
+            }
+        }
+        let pairs = data.pairs(side, file);
+        // Order the pieces as the table does
+        for i in lead_pawns..size - 1 {
+            if let Some(j) = (i + 1..size).find(|&j| pairs.pieces[i] == pieces[j]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+        // Mirror the board so the leading piece is on files a to d
+        if squares[0] % 8 > 3 {
+            for square in &mut squares[..size] {
+                *square ^= 7;
+            }
+        }
+        let mut index;
+        if info.has_pawns {
+            index = INDEXING.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| INDEXING.pawns[square]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                index += INDEXING.binomial[i][INDEXING.pawns[square]];
+            }
+        } else {
+            // Without pawns the board can be flipped to get the leading piece onto ranks 1 to
+            // 4 too, and mirrored in the diagonal to get the first piece of the leading group
+            // off it below it
+            if squares[0] / 8 > 3 {
+                for square in &mut squares[..size] {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..pairs.group_len[0] {
+                if off_diagonal(squares[i]) == 0 {
+                    continue;
+                }
+                if off_diagonal(squares[i]) > 0 {
+                    for square in &mut squares[i..size] {
+                        *square = flip_diagonal(*square);
+                    }
+                }
+                break;
+            }
+            index = if info.has_unique_pieces {
+                let adjust1 = (squares[1] > squares[0]) as usize;
+                let adjust2 = (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+                let rank = |square: usize| square / 8;
+                if off_diagonal(squares[0]) != 0 {
+                    ((INDEXING.a1_d1_d4[squares[0]] * 63 + squares[1] - adjust1) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[1]) != 0 {
+                    ((6 * 63 + rank(squares[0]) * 28 + INDEXING.b1_h1_h7[squares[1]]) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[2]) != 0 {
+                    (6 * 63 * 62 + 4 * 28 * 62 + rank(squares[0]) * 7 * 28 + (rank(squares[1]) - adjust1) * 28 + INDEXING.b1_h1_h7[squares[2]]) as u64
+                } else {
+                    (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(squares[0]) * 7 * 6 + (rank(squares[1]) - adjust1) * 6 + rank(squares[2]) - adjust2) as u64
+                }
+            } else {
+                INDEXING.kk[INDEXING.a1_d1_d4[squares[0]]][squares[1]] as u64
+            };
+        }
+        // The other groups follow, each piece counting only the squares the groups before it
+        // leave free
+        index *= pairs.group_idx[0];
+        let mut start = pairs.group_len[0];
+        let mut other_pawns = info.has_pawns && info.pawn_counts[1] > 0;
+        let mut next = 1;
+        while pairs.group_len[next] != 0 {
+            let len = pairs.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut group_index = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&earlier| square > earlier).count();
+                let free = square.saturating_sub(adjust + if other_pawns { 8 } else { 0 });
+                group_index += INDEXING.binomial[i + 1][free];
+            }
+            other_pawns = false;
+            index += group_index * pairs.group_idx[next];
+            start += len;
+            next += 1;
+        }
+        let value = pairs.decompress(&data.bytes, index)?;
+        if dtz {
+            data.map_score(file, value, wdl).map(TableValue::Value)
+        } else {
+            Some(TableValue::Value(value as i32 - 2))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexing_tables() {
+        let kk = INDEXING.kk.iter().flat_map(|row| row.iter()).max().unwrap();
+        assert_eq!(*kk, 461);
+        assert_eq!(INDEXING.binomial[2][5], 10);
+        assert_eq!(INDEXING.lead_pawns_size[1][0], 6);
+        assert_eq!(INDEXING.pawns[8], 47);
+        assert_eq!(INDEXING.pawns[15], 46);
+
+        let info = TableInfo::from_name("KRPvKP").unwrap();
+        assert!(info.has_pawns && info.has_unique_pieces && !info.symmetric);
+        assert_eq!(info.pawn_counts, [1, 1]);
+        assert!(TableInfo::from_name("KKvK").is_none());
+        assert_eq!(material(&Board::from_fen("8/8/8/4k3/8/8/1P6/R3K3 w - - 0 1").unwrap()), "KRPvK");
+    }
+
+    #[test]
+    fn probing_without_tables() {
+        let tablebases = Tablebases::open("/nonexistent");
+        assert!(tablebases.is_empty());
+        let bare_kings = Board::from_fen("8/8/8/4k3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&bare_kings), Some(Wdl::Draw));
+        let rook_ending = Board::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&rook_ending), None);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+    }
+
+    /// Tables in the directory the `WHALE_SYZYGY_PATH` variable names, which must hold at least
+    /// the WDL and DTZ files of KQvK, KRvK and KPvK. Without the variable the tests using them
+    /// pass without checking anything
+    fn three_piece_tables() -> Option<Tablebases> {
+        let path = std::env::var("WHALE_SYZYGY_PATH").ok()?;
+        let tablebases = Tablebases::open(&path);
+        assert!(tablebases.max_pieces() >= 3, "no tables in {}", path);
+        Some(tablebases)
+    }
+
+    #[test]
+    fn probing_three_piece_tables() {
+        let Some(tablebases) = three_piece_tables() else {
+            return;
+        };
+        let probe = |fen| {
+            let board = Board::from_fen(fen).unwrap();
+            (tablebases.probe_wdl(&board).unwrap(), tablebases.probe_dtz(&board).unwrap())
+        };
+        let (wdl, dtz) = probe("4k3/8/8/8/8/8/8/4K2Q w - - 0 1");
+        assert!(wdl == Wdl::Win && dtz > 0 && dtz < 100);
+        let (wdl, dtz) = probe("4k3/8/8/8/8/8/8/4K2Q b - - 0 1");
+        assert!(wdl == Wdl::Loss && dtz < 0 && dtz > -100);
+        // Black takes the queen, which the table does not store
+        assert_eq!(probe("8/8/8/8/8/5k2/6Q1/K7 b - - 0 1"), (Wdl::Draw, 0));
+        let (wdl, dtz) = probe("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        assert!(wdl == Wdl::Win && dtz > 0 && dtz < 100);
+        // The pawn runs, so the winning move is a pawn move, zeroing the counter at once
+        assert_eq!(probe("8/8/8/8/8/8/4P3/k3K3 w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe("8/8/8/8/8/8/4P3/k3K3 b - - 0 1").0, Wdl::Loss);
+        // The king in front of a rook pawn holds the draw
+        assert_eq!(probe("k7/8/8/8/8/8/P7/K7 w - - 0 1"), (Wdl::Draw, 0));
+    }
+
+    #[test]
+    fn ranking_root_moves_with_three_piece_tables() {
+        let Some(tablebases) = three_piece_tables() else {
+            return;
+        };
+        // The rook on a1 is attacked: moving it out of reach of the black king wins, anything
+        // else lets the king take it
+        let board = Board::from_fen("8/8/8/8/8/8/1k6/R3K3 w - - 0 1").unwrap();
+        let ranks = tablebases.rank_root_moves(&board).unwrap();
+        let mut winning: Vec<_> = ranks.iter().filter(|(_, rank)| *rank == MAX_DTZ).map(|(mv, _)| mv.to_uci()).collect();
+        winning.sort();
+        assert_eq!(winning, ["a1a4", "a1a5", "a1a6", "a1a7", "a1a8", "a1d1"]);
+        assert!(ranks.iter().all(|(_, rank)| *rank == MAX_DTZ || *rank == 0));
+        assert_eq!(ranks.len(), generate_all_legal_moves(&board).len());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::chess_parts::Move;
+use crate::chess_search::TB_WIN_BOUND;
+
+/// How a stored score relates to the true score of its position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    /// The score is exact
+    Exact,
+    /// The search failed high: the true score is at least this
+    Lower,
+    /// The search failed low: the true score is at most this
+    Upper,
+}
+
+/// What a search learnt about a position
+#[derive(Clone)]
+pub struct TtEntry {
+    /// Zobrist hash of the position, to tell it apart from others sharing the slot
+    key: u64,
+    /// Plies the position was searched to
+    pub depth: u32,
+    /// Score as stored, with mates counted from the position itself, see `score_to_tt`
+    pub score: i32,
+    /// What kind of bound `score` is
+    pub bound: Bound,
+    /// Best move found, or the one that caused the cutoff
+    pub best_move: Option<Move>,
+}
+
+/// Table of search results by position, so positions reached again through other move orders
+/// or in later iterations need not be searched again
+pub struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+}
+
+impl TranspositionTable {
+    /// Table using about `megabytes` of memory
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let count = (megabytes * 1024 * 1024 / std::mem::size_of::<Option<TtEntry>>()).max(1);
+        TranspositionTable { entries: vec![None; count] }
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+
+    /// What is known about the position hashing to `key`
+    pub fn probe(&self, key: u64) -> Option<&TtEntry> {
+        self.entries[self.slot(key)].as_ref().filter(|entry| entry.key == key)
+    }
+
+    /// Remember a search result, replacing what the slot held unless that is a deeper result
+    /// for the same position
+    pub fn store(&mut self, key: u64, depth: u32, score: i32, bound: Bound, best_move: Option<Move>) {
+        let slot = self.slot(key);
+        if let Some(existing) = &self.entries[slot] {
+            if existing.key == key && existing.depth > depth {
+                return;
+            }
+        }
+        self.entries[slot] = Some(TtEntry { key, depth, score, bound, best_move });
+    }
+
+    /// Permille of the table in use, estimated from its first thousand slots
+    pub fn hashfull(&self) -> u32 {
+        let sample = &self.entries[..self.entries.len().min(1000)];
+        (sample.iter().filter(|entry| entry.is_some()).count() * 1000 / sample.len()) as u32
+    }
+
+    /// Forget everything, as before a new game
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
+
+/// Score to store for a position `ply` moves from the root. Search scores count mates and
+/// tablebase wins from the root, but the same position can come up at any ply, so the table
+/// counts them from the position itself
+pub fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= TB_WIN_BOUND {
+        score + ply
+    } else if score <= -TB_WIN_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+/// Search score of a stored score, for the position `ply` moves from the root
+pub fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= TB_WIN_BOUND {
+        score - ply
+    } else if score <= -TB_WIN_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
@@ -0,0 +1,35 @@
+//! Chess rules, notation and game records for Whale, without any GUI
+//!
+//! ```
+//! use whale_core::chess_parts::Board;
+//! use whale_core::chess_engine::generate_all_legal_moves;
+//!
+//! assert_eq!(generate_all_legal_moves(&Board::default()).len(), 20);
+//! ```
+
+#![warn(missing_docs)]
+
+/// The board, pieces, squares and moves, and FEN
+pub mod chess_parts;
+/// Attack detection, legal move generation and perft
+pub mod chess_engine;
+/// Attack tables of the pieces, with magic bitboards for the sliders
+pub mod chess_attacks;
+/// A game with its move tree, tags, clocks and result
+pub mod chess_game;
+/// Time controls and chess clocks
+pub mod chess_clock;
+/// SAN, PGN and EPD
+pub mod chess_notation;
+/// ECO classification of openings
+pub mod chess_eco;
+/// Static evaluation of positions
+pub mod chess_eval;
+/// Search for the best move
+pub mod chess_search;
+/// Evaluation and search parameters settable at runtime
+pub mod chess_params;
+/// Transposition table of search results
+pub mod chess_tt;
+/// Syzygy endgame tablebase probing
+pub mod chess_syzygy;
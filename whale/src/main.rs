@@ -1,14 +1,34 @@
 mod whale_app;
-mod chess_engine;
-mod chess_parts;
 
 use whale_app::WhaleApp;
 use eframe;
+use whale_core::{chess_engine, chess_parts, chess_search};
 
 fn main() {
+    // Cross-check the legal move generator against the slow reference instead of starting the GUI
+    if std::env::args().any(|arg| arg == "--movegen-self-test") {
+        match chess_engine::movegen_self_test(&chess_parts::Board::default(), 100, 200, 1) {
+            Ok(()) => println!("movegen self-test passed"),
+            Err(divergence) => {
+                eprintln!("movegen self-test failed: {}", divergence);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    // Search the bench positions, optionally to the depth given after it, and report the nodes
+    // visited, which only a change to the search changes, and the speed
+    if std::env::args().nth(1).as_deref() == Some("bench") {
+        let depth = std::env::args().nth(2).and_then(|depth| depth.parse().ok()).unwrap_or(chess_search::BENCH_DEPTH);
+        let result = chess_search::bench(depth);
+        println!("Nodes searched  : {}", result.nodes);
+        println!("Time (ms)       : {}", result.time.as_millis());
+        println!("Nodes/second    : {}", result.nps());
+        return;
+    }
     let _ = eframe::run_native(
         "Whale Chess",
         eframe::NativeOptions::default(),
-        Box::new(|_cc| Ok(Box::<WhaleApp>::new(WhaleApp::new()))),
+        Box::new(|cc| Ok(Box::<WhaleApp>::new(WhaleApp::new(cc)))),
     );
 }
@@ -1,70 +1,237 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use eframe::{egui, App};
-use crate::chess_parts::*;
-use crate::chess_engine::*;
-
-#[allow(dead_code)]
-fn print_board(board: Board){
-    println!("  +---+---+---+---+---+---+---+---+");
-    for i in 0..8 {
-        print!("{} |", 8 - i);
-        for j in 0..8 {
-            let cell = board.cells[i * 8 + j];
-            if cell == 0 {
-                print!("   |");
-            } else {
-                let (piece, color) = piece_from_u8(cell);
-                let symbol = match piece {
-                    Piece::Pawn => 'P',
-                    Piece::Bishop => 'B',
-                    Piece::Rook => 'R',
-                    Piece::Knight => 'N',
-                    Piece::Queen => 'Q',
-                    Piece::King => 'K',
-                };
-                let display_char = if color.into() { symbol } else { symbol.to_ascii_lowercase() };
-                print!(" {} |", display_char);
-            }
-        }
-        println!("\n  +---+---+---+---+---+---+---+---+");
+use whale_core::chess_parts::*;
+use whale_core::chess_engine::*;
+use whale_core::chess_game::*;
+use whale_core::chess_notation::*;
+use whale_core::chess_clock::TimeControl;
+use whale_core::chess_syzygy::Tablebases;
+use whale_core::chess_eval::{evaluate_terms, Term, FULL_SCALE};
+use whale_core::chess_params::Params;
+use whale_core::chess_search::{allocate_time, Score, MAX_SKILL_LEVEL, SearchControl, SearchInfo, SearchLimits, SearchTask, TT_MEGABYTES};
+use whale_core::chess_tt::TranspositionTable;
+
+fn piece_texture_name(piece: Piece, color: Color) -> String {
+    let name = match piece {
+        Piece::Pawn => "pawn",
+        Piece::Rook => "rook",
+        Piece::Knight => "knight",
+        Piece::Bishop => "bishop",
+        Piece::Queen => "queen",
+        Piece::King => "king",
+    };
+    if color.into() {
+        format!("white_{}", name)
+    } else {
+        format!("black_{}", name)
     }
-    println!("    a   b   c   d   e   f   g   h");
 }
 
-fn piece_name_for_square(board: Board, row: usize, col: usize) -> Option<String> {
-    match board.cells[row * 8 + col] {
+fn piece_name_for_square(board: &Board, square: Mailbox64Index) -> Option<String> {
+    match board.cells()[square.0 as usize] {
         0 => None,
         cell => {
             let (piece, color) = piece_from_u8(cell);
-            let name = match piece {
-                Piece::Pawn => "pawn",
-                Piece::Rook => "rook",
-                Piece::Knight => "knight",
-                Piece::Bishop => "bishop",
-                Piece::Queen => "queen",
-                Piece::King => "king",
-            };
-            Some(if color.into() {
-                format!("white_{}", name)
-            } else {
-                format!("black_{}", name)
-            })
+            Some(piece_texture_name(piece, color))
         }
     }
 }
 
-pub(crate) struct WhaleApp {
+/// Lay out `nodes`, the moves playable in `board`, and everything after them like PGN movetext,
+/// with variations in parentheses. `path` leads to `board`, and the path of a clicked move ends
+/// up in `clicked`
+fn notation_line(ui: &mut egui::Ui, board: &Board, nodes: &[MoveNode], path: &[usize], current: &[usize], clicked: &mut Option<Vec<usize>>) {
+    let mut board = board.clone();
+    let mut nodes = nodes;
+    let mut path = path.to_vec();
+    let mut line_start = true;
+    while let Some(main) = nodes.first() {
+        path.push(0);
+        notation_move(ui, &board, main, &path, line_start, current, clicked);
+        line_start = main.comment.is_some() || nodes.len() > 1;
+        for (index, variation) in nodes.iter().enumerate().skip(1) {
+            ui.label("(");
+            *path.last_mut().unwrap() = index;
+            notation_move(ui, &board, variation, &path, true, current, clicked);
+            let mut after = board.clone();
+            after.make_move(&variation.mv);
+            notation_line(ui, &after, &variation.children, &path, current, clicked);
+            ui.label(")");
+        }
+        *path.last_mut().unwrap() = 0;
+        board.make_move(&main.mv);
+        nodes = &main.children;
+    }
+}
+
+fn notation_move(ui: &mut egui::Ui, board: &Board, node: &MoveNode, path: &[usize], line_start: bool, current: &[usize], clicked: &mut Option<Vec<usize>>) {
+    if let Some(comment) = &node.comment_before {
+        ui.label(egui::RichText::new(comment).italics().weak());
+    }
+    if board.turn() == Color::White {
+        ui.label(format!("{}.", board.fullmove_clock()));
+    } else if line_start || node.comment_before.is_some() {
+        ui.label(format!("{}...", board.fullmove_clock()));
+    }
+    // Glyphs judging the move are glued to it, those judging the position follow it
+    let assessment: String = node.nags.iter().filter(|nag| nag.is_move_assessment()).map(|nag| nag.to_string()).collect();
+    if ui.selectable_label(path == current, format!("{}{}", node.san, assessment)).clicked() {
+        *clicked = Some(path.to_vec());
+    }
+    for nag in node.nags.iter().filter(|nag| !nag.is_move_assessment()) {
+        ui.label(nag.to_string()).on_hover_text(nag.description());
+    }
+    if let Some(comment) = &node.comment {
+        ui.label(egui::RichText::new(comment).italics().weak());
+    }
+}
+
+/// One line per EPD operation the GUI knows about: best and avoided moves, evaluation and comment
+fn epd_summary(epd: &Epd) -> String {
+    let mut lines = Vec::new();
+    let san_list = |moves: &[Move]| moves.iter().map(|mv| to_san(&epd.board, mv)).collect::<Vec<_>>().join(", ");
+    if !epd.best_moves.is_empty() {
+        lines.push(format!("Best: {}", san_list(&epd.best_moves)));
+    }
+    if !epd.avoid_moves.is_empty() {
+        lines.push(format!("Avoid: {}", san_list(&epd.avoid_moves)));
+    }
+    if let Some(ce) = epd.centipawn_evaluation {
+        lines.push(format!("Evaluation: {:+.2}", ce as f32 / 100.0));
+    }
+    if let Some(comment) = epd.operation("c0") {
+        lines.push(comment.join(" "));
+    }
+    lines.join("\n")
+}
+
+/// Key of the current game in eframe's app storage
+const GAME_KEY: &str = "game";
+
+/// Time controls offered in the side panel, by name and as a PGN tag value
+const TIME_CONTROLS: [(&str, &str); 4] = [("3+2", "180+2"), ("5+0", "300"), ("15+10", "900+10"), ("40/90+30", "40/5400+30:1800+30")];
+
+/// Deepest the engine searches when it plays a move, and how long it may take in untimed games
+const ENGINE_DEPTH: u32 = 6;
+const ENGINE_THINKING_TIME: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How long the engine may think in the current position of `game`
+fn engine_thinking_time(game: &Game) -> std::time::Duration {
+    let turn = game.board().turn();
+    match (game.time_control(), game.clock(turn), game.remaining_time(turn)) {
+        (Some(control), Some(clock), Some(remaining)) => {
+            allocate_time(remaining, clock.period(control).increment, clock.moves_to_go(control))
+        },
+        _ => ENGINE_THINKING_TIME,
+    }
+}
+
+/// What the engine says about its search of `board`: score, depths, speed and the line it
+/// expects in SAN
+fn search_report(board: &Board, info: &SearchInfo) -> String {
+    let mut board = board.clone();
+    let mut pv = Vec::new();
+    for mv in info.lines.first().map_or(&[][..], |line| &line.moves[..]) {
+        pv.push(to_san(&board, mv));
+        board.make_move(mv);
+    }
+    format!(
+        "{} at depth {}/{}, {} kN at {} kN/s\n{}",
+        Score::new(info.score),
+        info.depth,
+        info.seldepth,
+        info.nodes / 1000,
+        info.nps / 1000,
+        pv.join(" "),
+    )
+}
+
+/// Search the engine runs on a worker thread
+struct EngineSearch {
+    task: SearchTask,
+    /// Position searched
     board: Board,
+    /// While the engine ponders, the hash of the position the user is thinking in. The search is
+    /// of the position after the reply the engine expects
+    waiting: Option<u64>,
+}
+
+impl EngineSearch {
+    fn start(board: Board, earlier_positions: Vec<u64>, limits: SearchLimits, control: SearchControl, tt: Arc<Mutex<TranspositionTable>>, waiting: Option<u64>) -> EngineSearch {
+        EngineSearch { task: SearchTask::spawn(board.clone(), earlier_positions, limits, control, tt), board, waiting }
+    }
+}
+
+/// How long a piece takes to slide to its new square when the position changes
+const SLIDE_SECONDS: f64 = 0.15;
+
+pub(crate) struct WhaleApp {
+    game: Game,
+    show_attacked_squares: bool,
+    /// Side the engine plays for, if any
+    engine_color: Option<Color>,
+    /// What the engine found in its current or last search
+    engine_report: Option<String>,
+    /// Whether the engine thinks on the user's time, how well it plays, and how much it wants to
+    /// avoid draws
+    engine_ponders: bool,
+    engine_skill: u8,
+    engine_contempt: i32,
+    engine_search: Option<EngineSearch>,
+    /// What the engine's searches learnt, kept from one move to the next until a new game
+    tt: Arc<Mutex<TranspositionTable>>,
+    /// Folders of Syzygy tablebases typed into the tablebase box, and the tablebases found there
+    tablebase_path: String,
+    tablebases: Option<Arc<Tablebases>>,
+    /// Config file of evaluation and search parameters typed into the parameter box, the
+    /// parameters the engine plays with, and why the file last failed to load
+    params_path: String,
+    params: Arc<Params>,
+    params_error: Option<String>,
+    /// From and to squares of a pawn move waiting for the user to pick the promotion piece
+    pending_promotion: Option<(Mailbox64Index, Mailbox64Index)>,
     image_bytes: Vec<(&'static str, &'static [u8])>,
     textures: HashMap<&'static str, egui::TextureHandle>,
-    dragging_piece: Option<(usize, usize)>,
+    dragging_piece: Option<Mailbox64Index>,
     drag_offset: egui::Vec2,
+    /// Board drawn in the last frame, and the pieces sliding away from it with when they started
+    shown_board: Board,
+    sliding: Option<(Vec<Slide>, f64)>,
+    /// PGN typed or pasted into the load box, and why it last failed to load
+    pgn_input: String,
+    pgn_error: Option<String>,
+    /// FEN or EPD typed or pasted into the position box, and why it last failed to load
+    position_input: String,
+    position_error: Option<String>,
+    /// What the last loaded EPD record says about its position
+    position_note: Option<String>,
 }
 
 impl WhaleApp {
-    pub(crate) fn new() -> Self {
+    /// Start with the game saved when the app was last closed, or a new one
+    pub(crate) fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let game = cc.storage
+            .and_then(|storage| eframe::get_value(storage, GAME_KEY))
+            .unwrap_or_else(|| Game::new(Board::default()));
         Self {
-            board: Board::default(),
+            shown_board: game.board().clone(),
+            sliding: None,
+            game,
+            show_attacked_squares: false,
+            engine_color: None,
+            engine_report: None,
+            engine_ponders: false,
+            engine_skill: MAX_SKILL_LEVEL,
+            engine_contempt: 0,
+            engine_search: None,
+            tt: Arc::new(Mutex::new(TranspositionTable::new(TT_MEGABYTES))),
+            tablebase_path: String::new(),
+            tablebases: None,
+            params_path: String::new(),
+            params: Arc::default(),
+            params_error: None,
+            pending_promotion: None,
             image_bytes: vec![
                 ("white_pawn", include_bytes!("assets/white-pawn.png")),
                 ("black_pawn", include_bytes!("assets/black-pawn.png")),
@@ -82,11 +249,29 @@ impl WhaleApp {
             textures: HashMap::new(),
             dragging_piece: None,
             drag_offset: egui::Vec2::ZERO,
+            pgn_input: String::new(),
+            pgn_error: None,
+            position_input: String::new(),
+            position_error: None,
+            position_note: None,
         }
     }
+
+    /// Stop the engine and clear what it learnt, as a new game starts
+    fn forget_engine_search(&mut self) {
+        if let Some(search) = self.engine_search.take() {
+            search.task.control().stop();
+        }
+        // Once stopped, the searches still running let go of the table soon
+        self.tt.lock().expect("engine search panicked").clear();
+    }
 }
 
 impl App for WhaleApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, GAME_KEY, &self.game);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if self.textures.is_empty() {
             for (name, bytes) in &self.image_bytes {
@@ -101,15 +286,353 @@ impl App for WhaleApp {
             }
         }
 
+        let engine_to_move = self.engine_color == Some(self.game.board().turn());
+        let ongoing = self.game.result() == GameResult::Ongoing;
+        if let Some(mut search) = self.engine_search.take() {
+            let position = self.game.board().zobrist();
+            let keep = match search.waiting {
+                Some(waiting) if position == waiting => self.engine_color.is_some() && self.engine_ponders && !engine_to_move,
+                _ if position == search.board.zobrist() && engine_to_move && ongoing => {
+                    if search.waiting.take().is_some() {
+                        search.task.control().ponderhit(engine_thinking_time(&self.game));
+                    }
+                    true
+                },
+                _ => false,
+            };
+            if keep {
+                self.engine_search = Some(search);
+            } else {
+                // Another move, an undo or a new game: the search is of no use, and its thread
+                // ends on its own once stopped
+                search.task.control().stop();
+            }
+        }
+        if engine_to_move && ongoing && self.engine_search.is_none() {
+            let limits = SearchLimits {
+                move_time: Some(engine_thinking_time(&self.game)),
+                contempt: self.engine_contempt,
+                tablebases: self.tablebases.clone(),
+                params: self.params.clone(),
+                ..SearchLimits::depth(ENGINE_DEPTH)
+            }
+            .with_skill_level(self.engine_skill);
+            let earlier_positions = self.game.history().earlier_positions();
+            self.engine_search = Some(EngineSearch::start(self.game.board().clone(), earlier_positions, limits, SearchControl::default(), self.tt.clone(), None));
+        }
+        if let Some(search) = &mut self.engine_search {
+            if let Some(info) = search.task.latest_info() {
+                let doing = if search.waiting.is_some() { "Pondering" } else { "Engine" };
+                self.engine_report = Some(format!("{}: {}", doing, search_report(&search.board, info)));
+            }
+        }
+        let finished = self.engine_search.as_ref().is_some_and(|search| search.waiting.is_none() && search.task.is_finished());
+        if finished && self.sliding.is_none() && self.dragging_piece.is_none() {
+            let result = self.engine_search.take().unwrap().task.join();
+            if let Some(mv) = &result.best_move {
+                self.game.make_move(mv);
+                if let Some(reply) = result.ponder_move().filter(|_| self.engine_ponders && self.game.result() == GameResult::Ongoing) {
+                    let mut board = self.game.board().clone();
+                    let waiting = board.zobrist();
+                    let mut earlier_positions = self.game.history().earlier_positions();
+                    earlier_positions.push(board.repetition_hash());
+                    board.make_move(reply);
+                    let limits = SearchLimits {
+                        contempt: self.engine_contempt,
+                        tablebases: self.tablebases.clone(),
+                        params: self.params.clone(),
+                        ..SearchLimits::depth(ENGINE_DEPTH)
+                    }.with_skill_level(self.engine_skill);
+                    self.engine_search = Some(EngineSearch::start(board, earlier_positions, limits, SearchControl::pondering(), self.tt.clone(), Some(waiting)));
+                }
+            }
+            self.pending_promotion = None;
+        }
+
+        let result = self.game.result();
+        let game_over = result != GameResult::Ongoing;
         egui::SidePanel::left("side_panel").width_range(egui::Rangef::new(200.0, 500.0)).resizable(true).show(ctx, |ui| {
             ui.heading("Whale Chess");
+            let side_to_move = if self.game.board().turn().into() { "White" } else { "Black" };
+            ui.label(format!("Move {}, {} to play", self.game.board().fullmove_clock(), side_to_move));
+            if let Some(opening) = self.game.opening() {
+                ui.label(format!("{} {}", opening.eco, opening.name));
+            }
+            if let Some(report) = &self.engine_report {
+                ui.label(report);
+            }
+            // What the static evaluation makes of the position, term by term
+            ui.collapsing("Static evaluation", |ui| {
+                let breakdown = evaluate_terms(self.game.board(), &self.params.eval);
+                egui::Grid::new("evaluation_terms").striped(true).show(ui, |ui| {
+                    for heading in ["", "White", "Black", "Total"] {
+                        ui.label(heading);
+                    }
+                    ui.end_row();
+                    for term in Term::ALL {
+                        ui.label(term.name());
+                        ui.label(breakdown.term(term, Color::White).to_string());
+                        ui.label(breakdown.term(term, Color::Black).to_string());
+                        ui.label(breakdown.difference(term).to_string());
+                        ui.end_row();
+                    }
+                });
+                if breakdown.scale != FULL_SCALE {
+                    ui.label(format!("Drawish material, scaled by {}/{}", breakdown.scale, FULL_SCALE));
+                }
+                ui.label(format!("{:+.2} for White", breakdown.white_total() as f64 / 100.0));
+            });
+            if self.game.time_control().is_some() {
+                for (color, name) in [(Color::White, "White"), (Color::Black, "Black")] {
+                    let remaining = self.game.remaining_time(color).unwrap_or_default().as_secs();
+                    ui.label(format!("{}: {}:{:02}", name, remaining / 60, remaining % 60));
+                }
+                if !game_over {
+                    ctx.request_repaint_after(std::time::Duration::from_millis(100));
+                }
+            }
+            ui.collapsing("Time control", |ui| {
+                ui.horizontal(|ui| {
+                    for (label, pgn) in TIME_CONTROLS {
+                        if ui.button(label).clicked() {
+                            self.game.set_time_control(TimeControl::from_pgn(pgn).unwrap());
+                        }
+                    }
+                });
+            });
+            ui.horizontal(|ui| {
+                let mut new_game = None;
+                if ui.button("New game").clicked() {
+                    new_game = Some(Game::new(Board::default()));
+                }
+                if ui.button("New Chess960 game").clicked() {
+                    new_game = Some(Game::random_chess960());
+                }
+                if let Some(game) = new_game {
+                    self.forget_engine_search();
+                    self.game = game;
+                    self.pending_promotion = None;
+                    self.dragging_piece = None;
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Copy FEN").clicked() {
+                    ctx.copy_text(self.game.board().to_fen());
+                }
+                if ui.button("Copy PGN").clicked() {
+                    ctx.copy_text(write_pgn(&self.game));
+                }
+            });
+            ui.collapsing("Load FEN or EPD", |ui| {
+                ui.text_edit_singleline(&mut self.position_input);
+                if ui.button("Load").clicked() {
+                    // A FEN has the move clocks where an EPD has its operations
+                    let loaded = Board::from_fen(&self.position_input).map(|board| (board, None)).or_else(|fen_error| {
+                        read_epd(&self.position_input).map(|epd| (epd.board.clone(), Some(epd))).map_err(|_| fen_error.to_string())
+                    });
+                    match loaded {
+                        Ok((board, epd)) => {
+                            self.forget_engine_search();
+                            self.game = Game::new(board);
+                            if let Some(id) = epd.as_ref().and_then(|epd| epd.id.clone()) {
+                                self.game.set_tag("Event", &id);
+                            }
+                            self.position_note = epd.as_ref().map(epd_summary);
+                            self.pending_promotion = None;
+                            self.dragging_piece = None;
+                            self.position_error = None;
+                        },
+                        Err(error) => self.position_error = Some(error),
+                    }
+                }
+                if let Some(error) = &self.position_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                if let Some(note) = &self.position_note {
+                    ui.label(note);
+                }
+            });
+            ui.collapsing("Load PGN", |ui| {
+                ui.add(egui::TextEdit::multiline(&mut self.pgn_input).desired_rows(6));
+                if ui.button("Load").clicked() {
+                    match read_pgn(&self.pgn_input) {
+                        Ok(game) => {
+                            self.forget_engine_search();
+                            self.game = game;
+                            self.pending_promotion = None;
+                            self.dragging_piece = None;
+                            self.pgn_error = None;
+                        },
+                        Err(error) => self.pgn_error = Some(error.to_string()),
+                    }
+                }
+                if let Some(error) = &self.pgn_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
+            for color in [Color::White, Color::Black] {
+                let captured = self.game.captured_pieces(color);
+                ui.horizontal(|ui| {
+                    for piece in captured {
+                        if let Some(texture) = self.textures.get(piece_texture_name(piece, color).as_str()) {
+                            ui.add(egui::Image::new(texture).fit_to_exact_size(egui::vec2(20.0, 20.0)));
+                        }
+                    }
+                });
+            }
+            if game_over {
+                ui.label(result.to_string());
+            } else {
+                let side_to_move = self.game.board().turn();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!self.game.moves().is_empty(), egui::Button::new("Take back")).clicked() {
+                        self.game.undo();
+                        self.pending_promotion = None;
+                    }
+                    if ui.button("Resign").clicked() {
+                        self.game.resign(side_to_move);
+                    }
+                    match self.game.draw_offer() {
+                        Some(offered_by) if offered_by != side_to_move => {
+                            if ui.button("Accept draw").clicked() {
+                                self.game.accept_draw(side_to_move);
+                            }
+                            if ui.button("Decline draw").clicked() {
+                                self.game.decline_draw(side_to_move);
+                            }
+                        },
+                        Some(_) => {
+                            ui.label("Draw offered");
+                        },
+                        None => {
+                            if ui.button("Offer draw").clicked() {
+                                self.game.offer_draw(side_to_move);
+                            }
+                        },
+                    }
+                });
+            }
         });
         egui::SidePanel::right("right_panel").width_range(egui::Rangef::new(200.0, 500.0)).resizable(true).show(ctx, |ui| {
             ui.heading("Whale Chess - Right Panel");
+            ui.checkbox(&mut self.show_attacked_squares, "Show squares attacked by the opponent");
+            ui.horizontal(|ui| {
+                ui.label("Engine plays");
+                ui.radio_value(&mut self.engine_color, None, "Nobody");
+                ui.radio_value(&mut self.engine_color, Some(Color::White), "White");
+                ui.radio_value(&mut self.engine_color, Some(Color::Black), "Black");
+            });
+            ui.checkbox(&mut self.engine_ponders, "Engine thinks on your time");
+            ui.add(egui::Slider::new(&mut self.engine_skill, 0..=MAX_SKILL_LEVEL).text("Engine strength"));
+            ui.horizontal(|ui| {
+                ui.label("Contempt")
+                    .on_hover_text("Centipawns the engine gives up to avoid a draw, or to get one when negative");
+                ui.add(egui::DragValue::new(&mut self.engine_contempt).range(-200..=200));
+            });
+            ui.collapsing("Endgame tablebases", |ui| {
+                ui.text_edit_singleline(&mut self.tablebase_path)
+                    .on_hover_text("Folders of Syzygy .rtbw and .rtbz files, separated like in PATH");
+                if ui.button("Load").clicked() {
+                    let tablebases = Tablebases::open(&self.tablebase_path);
+                    self.tablebases = (!tablebases.is_empty()).then(|| Arc::new(tablebases));
+                }
+                match &self.tablebases {
+                    Some(tablebases) => ui.label(format!("{} tables, up to {} pieces", tablebases.len(), tablebases.max_pieces())),
+                    None => ui.label("No tables loaded"),
+                };
+            });
+            ui.collapsing("Engine parameters", |ui| {
+                ui.text_edit_singleline(&mut self.params_path)
+                    .on_hover_text("File of evaluation and search parameters, one name and value per line, like whale-tune prints");
+                ui.horizontal(|ui| {
+                    if ui.button("Load").clicked() {
+                        let loaded = std::fs::read_to_string(&self.params_path)
+                            .map_err(|error| error.to_string())
+                            .and_then(|config| Params::from_config(&config).map_err(|error| error.to_string()));
+                        match loaded {
+                            Ok(params) => {
+                                self.params = Arc::new(params);
+                                self.params_error = None;
+                            },
+                            Err(error) => self.params_error = Some(error),
+                        }
+                    }
+                    if ui.button("Reset").clicked() {
+                        self.params = Arc::default();
+                        self.params_error = None;
+                    }
+                });
+                if let Some(error) = &self.params_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                } else if *self.params != Params::default() {
+                    ui.label("Playing with loaded parameters");
+                } else {
+                    ui.label("Playing with the default parameters");
+                }
+            });
+            if let Some(search) = self.engine_search.as_ref().filter(|search| search.waiting.is_none()) {
+                ui.horizontal(|ui| {
+                    if ui.button("Move now").clicked() {
+                        search.task.control().stop();
+                    }
+                    if ui.button("Stop engine").clicked() {
+                        search.task.control().stop();
+                        self.engine_color = None;
+                    }
+                });
+            }
+            ui.separator();
+            let path = self.game.path().to_vec();
+            let mut clicked = None;
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!path.is_empty(), egui::Button::new("<")).clicked() {
+                    self.game.undo();
+                    self.pending_promotion = None;
+                }
+                if ui.button(">").clicked() {
+                    self.game.redo();
+                    self.pending_promotion = None;
+                }
+            });
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    notation_line(ui, self.game.start(), self.game.tree(), &[], &path, &mut clicked);
+                });
+            });
+            if let Some(path) = clicked {
+                self.game.go_to(&path);
+                self.pending_promotion = None;
+            }
         });
         egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
             ui.heading("Whale Chess - Bottom Panel");
         });
+        if let Some((from, to)) = self.pending_promotion {
+            egui::Window::new("Promote to")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        for piece in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+                            let Some(texture) = self.textures.get(piece_texture_name(piece, self.game.board().turn()).as_str()) else {
+                                continue;
+                            };
+                            let image = egui::Image::new(texture).fit_to_exact_size(egui::vec2(48.0, 48.0));
+                            if ui.add(egui::Button::image(image)).clicked() {
+                                let mv = Move::from_squares(self.game.board(), from, to, Some(piece));
+                                if let Some(mv) = legal_move(self.game.board(), &mv) {
+                                    self.game.make_move(&mv);
+                                }
+                                self.pending_promotion = None;
+                            }
+                        }
+                    });
+                    if ui.button("Cancel").clicked() {
+                        self.pending_promotion = None;
+                    }
+                });
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             let rect = ui.available_rect_before_wrap();
             let painter = ui.painter().clone();
@@ -120,63 +643,99 @@ impl App for WhaleApp {
             let square_size = side / board_size as f32;
 
             if square_size > 0.0 {
+                let now = ui.input(|input| input.time);
+                if self.shown_board.cells() != self.game.board().cells() {
+                    self.sliding = Some((self.shown_board.slides(self.game.board()), now));
+                    self.shown_board = self.game.board().clone();
+                }
+                let progress = self.sliding.as_ref().map_or(1.0, |(_, start)| ((now - start) / SLIDE_SECONDS).min(1.0) as f32);
+                if progress >= 1.0 {
+                    self.sliding = None;
+                }
+                let sliding_to = self.sliding.as_ref().map_or(0, |(slides, _)| slides.iter().fold(0u64, |mask, slide| mask | (1 << slide.to.0)));
                 let color_a = egui::Color32::from_rgb(255, 238, 215);
                 let color_b = egui::Color32::from_rgb(58, 34, 0);
+                let side_to_move = self.game.board().turn();
+                let checked_king = if is_in_check(self.game.board(), side_to_move) {
+                    find_king(self.game.board(), side_to_move)
+                } else {
+                    None
+                };
+                let last_move = self.game.moves().last().map(|mv| (1u64 << mv.from.0) | (1u64 << mv.to.0)).unwrap_or(0);
+                let attacked = if self.show_attacked_squares {
+                    let opponent = side_to_move.opposite();
+                    attacked_squares(self.game.board(), opponent)
+                } else {
+                    0
+                };
 
-                for row in 0..board_size {
-                    for col in 0..board_size {
-                        let x = top_left.x + col as f32 * square_size;
-                        let y = top_left.y + row as f32 * square_size;
-                        let rect = egui::Rect::from_min_max(
-                            egui::pos2(x, y),
-                            egui::pos2(x + square_size, y + square_size),
-                        );
-                        let color = if (row + col) % 2 == 0 { color_a } else { color_b };
+                // White plays up the screen, so the eighth rank is the top row
+                let square_rect = |square: Mailbox64Index| {
+                    let min = top_left + egui::vec2(square.file().index() as f32, (7 - square.rank().index()) as f32) * square_size;
+                    egui::Rect::from_min_size(min, egui::vec2(square_size, square_size))
+                };
+                let square_at = |pos: egui::Pos2| {
+                    let offset = (pos - top_left) / square_size;
+                    if offset.x < 0.0 || offset.y < 0.0 {
+                        return None;
+                    }
+                    let file = File::from_index(offset.x.floor() as u8)?;
+                    let rank = Rank::from_index(7u8.checked_sub(offset.y.floor() as u8)?)?;
+                    Some(Mailbox64Index::from_file_rank(file, rank))
+                };
+
+                for rank in Rank::ALL.into_iter().rev() {
+                    for file in File::ALL {
+                        let square = Mailbox64Index::from_file_rank(file, rank);
+                        let rect = square_rect(square);
+                        let color = if (file.index() + rank.index()) % 2 == 1 { color_a } else { color_b };
                         painter.rect_filled(rect, 0.0, color);
-                        if let Some(piece_name) = piece_name_for_square(self.board.clone(), row, col) {
+                        if last_move & (1 << square.0) != 0 {
+                            painter.rect_filled(rect, 0.0, egui::Color32::from_rgba_unmultiplied(255, 255, 0, 70));
+                        }
+                        if attacked & (1 << square.0) != 0 {
+                            painter.rect_filled(rect, 0.0, egui::Color32::from_rgba_unmultiplied(255, 140, 0, 90));
+                        }
+                        if checked_king == Some(square) {
+                            painter.rect_filled(rect, 0.0, egui::Color32::from_rgba_unmultiplied(255, 0, 0, 120));
+                        }
+                        if let Some(piece_name) = piece_name_for_square(self.game.board(), square) {
                             if let Some(texture) = self.textures.get(piece_name.as_str()) {
-                                let image_rect = egui::Rect::from_min_max(
-                                    egui::pos2(x, y),
-                                    egui::pos2(x + square_size, y + square_size),
-                                );
-                                if Some((row, col)) != self.dragging_piece {
+                                if Some(square) != self.dragging_piece && sliding_to & (1 << square.0) == 0 {
                                     painter.image(
                                         texture.id(),
-                                        image_rect,
+                                        rect,
                                         egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
                                         egui::Color32::WHITE,
                                     );
                                 }
-                                let piece_response = ui.interact(image_rect, ui.id().with((row, col)), egui::Sense::click_and_drag());
-                                if piece_response.drag_started() {
-                                    self.dragging_piece = Some((row, col));
+                                let piece_response = ui.interact(rect, ui.id().with(square.0), egui::Sense::click_and_drag());
+                                let (_, piece_color) = piece_from_u8(self.game.board().cells()[square.0 as usize]);
+                                if piece_response.drag_started() && !game_over && piece_color == self.game.board().turn() && self.pending_promotion.is_none() {
+                                    self.dragging_piece = Some(square);
                                     if let Some(pointer) = ui.ctx().pointer_interact_pos() {
                                         self.drag_offset = pointer - rect.min;
                                     }
                                 }
                                 if piece_response.drag_stopped() {
-                                    let pointer_pos = piece_response.interact_pointer_pos();
-                                    if pointer_pos.is_none() {
-                                        continue;
-                                    }
-                                    let pointer_pos = pointer_pos.unwrap();
-                                    let rel_x = pointer_pos.x - top_left.x;
-                                    let rel_y = pointer_pos.y - top_left.y;
-                                    if rel_x < 0.0 || rel_y < 0.0 {
-                                        self.dragging_piece = None;
-                                        continue;
-                                    }
-                                    let new_col = (rel_x / square_size).floor() as usize;
-                                    let new_row = (rel_y / square_size).floor() as usize;
-                                    if new_row < 8 && new_col < 8 {
-                                        if let Some((old_row, old_col)) = self.dragging_piece {
-                                            if old_row == new_row && old_col == new_col {
-                                                self.dragging_piece = None;
-                                                continue;
+                                    let dropped_on = piece_response.interact_pointer_pos().and_then(square_at);
+                                    if let (Some(from), Some(to)) = (self.dragging_piece, dropped_on) {
+                                        if from != to {
+                                            let (piece, _) = piece_from_u8(self.game.board().cells()[from.0 as usize]);
+                                            if piece == Piece::Pawn && (to.rank() == Rank::First || to.rank() == Rank::Eighth) {
+                                                // Ask for the piece once we know the pawn may go there at all
+                                                let mv = Move::from_squares(self.game.board(), from, to, Some(Piece::Queen));
+                                                if is_legal(self.game.board(), &mv) {
+                                                    self.pending_promotion = Some((from, to));
+                                                }
+                                            } else {
+                                                let mv = Move::from_squares(self.game.board(), from, to, None);
+                                                if let Some(mv) = legal_move(self.game.board(), &mv) {
+                                                    self.game.make_move(&mv);
+                                                    // The piece was dropped where it belongs, so there is nothing to slide
+                                                    self.shown_board = self.game.board().clone();
+                                                }
                                             }
-                                            let piece = self.board.cells[old_row * 8 + old_col];
-                                            self.board.cells[new_row * 8 + new_col] = piece;
-                                            self.board.cells[old_row * 8 + old_col] = 0;
                                         }
                                     }
                                     self.dragging_piece = None;
@@ -185,8 +744,24 @@ impl App for WhaleApp {
                         }
                     }
                 }
-                if let Some((row, col)) = self.dragging_piece {
-                    let piece_name = piece_name_for_square(self.board.clone(), row, col).unwrap();
+                if let Some((slides, _)) = &self.sliding {
+                    for slide in slides {
+                        let (piece, color) = piece_from_u8(slide.cell);
+                        let Some(texture) = self.textures.get(piece_texture_name(piece, color).as_str()) else {
+                            continue;
+                        };
+                        let pos = square_rect(slide.from).min.lerp(square_rect(slide.to).min, progress);
+                        painter.image(
+                            texture.id(),
+                            egui::Rect::from_min_size(pos, egui::vec2(square_size, square_size)),
+                            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                            egui::Color32::WHITE,
+                        );
+                    }
+                    ui.ctx().request_repaint();
+                }
+                if let Some(square) = self.dragging_piece {
+                    let piece_name = piece_name_for_square(self.game.board(), square).unwrap();
                     if let Some(texture) = self.textures.get(piece_name.as_str()) {
                         if let Some(pointer) = ui.ctx().pointer_interact_pos() {
                             let pos = pointer - self.drag_offset;
@@ -199,20 +774,18 @@ impl App for WhaleApp {
                             );
                         }
                     }
-                    let pseudolegal_moves = generate_legal(&self.board, Mailbox64Index((row * 8 + col) as u8));
-                    for target_index in pseudolegal_moves {
-                        let target_row = (target_index.0 / 8) as usize;
-                        let target_col = (target_index.0 % 8) as usize;
-                        let x = top_left.x + target_col as f32 * square_size;
-                        let y = top_left.y + target_row as f32 * square_size;
-                        let rect = egui::Rect::from_min_max(
-                            egui::pos2(x, y),
-                            egui::pos2(x + square_size, y + square_size),
-                        );
-                        painter.rect_filled(rect, 0.0, egui::Color32::from_rgba_unmultiplied(0, 255, 0, 100));
+                    for mv in generate_legal(self.game.board(), square) {
+                        painter.rect_filled(square_rect(mv.to), 0.0, egui::Color32::from_rgba_unmultiplied(0, 255, 0, 100));
                     }
                 }
             }
         });
+        // Keep showing the engine's progress, and let it answer a move made this frame without
+        // waiting for more input
+        if self.engine_search.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        } else if self.engine_color == Some(self.game.board().turn()) && self.game.result() == GameResult::Ongoing {
+            ctx.request_repaint();
+        }
     }
 }